@@ -1,11 +1,32 @@
 use clap::{ArgGroup, Parser};
 use kube::Client;
+use lettre::message::Mailbox;
 
-use k8s_notifier::namespace::NamespaceScope;
-use k8s_notifier::notifier::log::LogNotifier;
-use k8s_notifier::notifier::slack::SlackNotifier;
+use k8s_notifier::logs::ContainerLogOptions;
+use k8s_notifier::namespace::{NamespacePattern, NamespaceScope};
+use k8s_notifier::notifier::alertmanager::AlertmanagerNotifier;
+use k8s_notifier::notifier::discord::DiscordNotifier;
+use k8s_notifier::notifier::email::{EmailConfig, EmailNotifier, EmailRecipients, SmtpTls};
+use k8s_notifier::notifier::file::{FileConfig, FileNotifier};
+#[cfg(feature = "kafka")]
+use k8s_notifier::notifier::kafka::KafkaNotifier;
+use k8s_notifier::notifier::log::{LogFormat, LogNotifier};
+use k8s_notifier::notifier::mattermost::MattermostNotifier;
+#[cfg(feature = "nats")]
+use k8s_notifier::notifier::nats::NatsNotifier;
+use k8s_notifier::notifier::opsgenie::{self, OpsgenieNotifier};
+use k8s_notifier::notifier::pagerduty::{self, PagerDutyNotifier};
+use k8s_notifier::notifier::slack::{self, SlackNotifier, SlackRoute, SlackTarget};
+use k8s_notifier::notifier::teams::TeamsNotifier;
+use k8s_notifier::notifier::webhook::{
+    WebhookAuth, WebhookConfig, WebhookFormat, WebhookHeader, WebhookNotifier,
+};
 use k8s_notifier::notifier::{Notifier, NotifierLogLevel, NotifierType};
 use k8s_notifier::resource::WatchedResource;
+use k8s_notifier::selector::{
+    validate_label_selector, FieldSelector, LabelSelector, ResourceSelectors,
+};
+use k8s_notifier::state::StateTracker;
 use k8s_notifier::ResourceWatcher;
 
 /// A cluster utility that watches objects based on registered interest
@@ -15,16 +36,28 @@ use k8s_notifier::ResourceWatcher;
 #[clap(
     author = "Rohan Krishnaswamy <rohan@fastmail.us>",
     group(
-        ArgGroup::new("namespace_scope").required(true).args(&["namespaces", "all_namespaces"]),
+        ArgGroup::new("namespace_scope").required(true).args(&["namespaces", "all_namespaces", "namespace_selector"]),
     ),
     group(
         ArgGroup::new("resource").required(true).args(&["resources"])
     )
 )]
 struct CliArgs {
-    /// Resources to monitor
+    /// Resources to monitor. One of node, pod, event, deployment, statefulset, daemonset,
+    /// job, cronjob or an arbitrary resource in the form `<group>/<version>/<kind>`
+    /// (e.g. `cert-manager.io/v1/Certificate`)
     #[arg(long, short, num_args = 1.., value_delimiter = ' ', env)]
     resources: Vec<WatchedResource>,
+    /// Label selectors restricting which objects are watched, separated by `;`. A
+    /// selector may be scoped to a single resource by prefixing it with the resource
+    /// and a colon (e.g. `pod:team=payments`), otherwise it applies to all resources
+    #[arg(long, env, value_delimiter = ';')]
+    label_selector: Vec<LabelSelector>,
+    /// Field selectors restricting which objects are watched, separated by `;`. A
+    /// selector may be scoped to a single resource by prefixing it with the resource
+    /// and a colon (e.g. `event:type!=Normal`), otherwise it applies to all resources
+    #[arg(long, env, value_delimiter = ';')]
+    field_selector: Vec<FieldSelector>,
     /// Namespaces in which non cluster-scoped resources should be monitored
     #[arg(long, short, num_args = 1.., value_delimiter = ' ', env)]
     namespaces: Option<Vec<String>>,
@@ -34,58 +67,483 @@ struct CliArgs {
     /// Watch resources in all namespaces
     #[arg(long, env)]
     all_namespaces: bool,
-    /// Slack API token. Required if 'slack' is configured as a notifier
-    #[arg(long, env)]
+    /// Watch resources in namespaces matching a label selector (e.g. `notify=true`).
+    /// Namespaces are picked up as they're created or labelled
+    #[arg(long, env, value_parser = parse_namespace_selector)]
+    namespace_selector: Option<String>,
+    /// Glob patterns of namespaces to ignore when watching all namespaces or
+    /// namespaces matching a selector (e.g. `kube-* cert-manager ci-*`)
+    #[arg(long, num_args = 1.., value_delimiter = ' ', env, conflicts_with = "namespaces")]
+    exclude_namespaces: Vec<NamespacePattern>,
+    /// Slack bot token. Either this and a channel, or an incoming webhook URL, are
+    /// required if 'slack' is configured as a notifier
+    #[arg(long, env, requires = "slack_channel")]
     slack_token: Option<String>,
-    /// Slack API token. Required if 'slack' is configured as a notifier
-    #[arg(long, env)]
+    /// Slack channel ID to post to with the bot token, when no route claims a
+    /// notification
+    #[arg(long, env, requires = "slack_token")]
     slack_channel: Option<String>,
+    /// Rules routing Slack notifications to channels, separated by `;`. Each route is
+    /// a `|` separated list of `channels`, `namespaces`, `selector`, `kinds`, `level`
+    /// and `continue` fields, e.g. `namespaces=payments-*|channels=C0123`. Matching
+    /// routes stop routing unless marked `continue`, e.g.
+    /// `level=error|channels=C0456|continue`
+    #[arg(long, env, value_delimiter = ';', requires = "slack_token")]
+    slack_route: Vec<SlackRoute>,
+    /// Slack incoming webhook URL, used instead of a bot token and channel. Messages
+    /// posted through a webhook can't be threaded
+    #[arg(long, env, conflicts_with_all = ["slack_token", "slack_channel"])]
+    slack_webhook_url: Option<reqwest::Url>,
+    /// Slack Web API base URL
+    #[arg(long, env, default_value = slack::DEFAULT_API_URL)]
+    slack_api_url: reqwest::Url,
+    /// Alertmanager base URL (e.g. `http://alertmanager:9093`). Required if
+    /// 'alertmanager' is configured as a notifier
+    #[arg(long, env)]
+    alertmanager_url: Option<reqwest::Url>,
+    /// How often, in seconds, active alerts are re-sent to Alertmanager. Must be lower
+    /// than Alertmanager's `resolve_timeout`
+    #[arg(long, env, default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+    alertmanager_resend_interval: u64,
+    /// The minimum level of notification which triggers an Alertmanager alert,
+    /// regardless of the notifier log level
+    #[arg(long, env, default_value_t = NotifierLogLevel::Error)]
+    alertmanager_min_level: NotifierLogLevel,
+    /// Discord webhook URL. Required if 'discord' is configured as a notifier
+    #[arg(long, env)]
+    discord_webhook_url: Option<reqwest::Url>,
+    /// File to append notifications to as JSON Lines. Required if 'file' is
+    /// configured as a notifier
+    #[arg(long, env)]
+    file_path: Option<std::path::PathBuf>,
+    /// Rotate the notifications file once it would grow beyond this many bytes
+    #[arg(long, env)]
+    file_max_bytes: Option<u64>,
+    /// Rotate the notifications file once it has been written to for this many
+    /// seconds
+    #[arg(long, env)]
+    file_max_age: Option<u64>,
+    /// The number of rotated notifications files to keep
+    #[arg(long, env, default_value_t = 5)]
+    file_max_files: usize,
+    /// Gzip rotated notifications files
+    #[arg(long, env)]
+    file_compress: bool,
+    /// Comma separated Kafka brokers (e.g. `kafka-0:9092,kafka-1:9092`). Required if
+    /// 'kafka' is configured as a notifier
+    #[cfg(feature = "kafka")]
+    #[arg(long, env)]
+    kafka_brokers: Option<String>,
+    /// Kafka topic to publish notifications to. Required if 'kafka' is configured as
+    /// a notifier
+    #[cfg(feature = "kafka")]
+    #[arg(long, env)]
+    kafka_topic: Option<String>,
+    /// Additional librdkafka producer properties in the form `<key>=<value>`,
+    /// separated by `;` (e.g. `security.protocol=SASL_SSL;sasl.mechanism=PLAIN`)
+    #[cfg(feature = "kafka")]
+    #[arg(long, env, value_delimiter = ';', value_parser = parse_kafka_property)]
+    kafka_config: Vec<(String, String)>,
+    /// Mattermost incoming webhook URL. Required if 'mattermost' is configured as a
+    /// notifier
+    #[arg(long, env)]
+    mattermost_webhook_url: Option<reqwest::Url>,
+    /// Mattermost channel to post to, overriding the webhook's default channel
+    #[arg(long, env)]
+    mattermost_channel: Option<String>,
+    /// SMTP server to send emails through. Required if 'email' is configured as a
+    /// notifier
+    #[arg(long, env)]
+    smtp_host: Option<String>,
+    /// SMTP server port. Defaults to 587 for STARTTLS, 465 for TLS and 25 otherwise
+    #[arg(long, env)]
+    smtp_port: Option<u16>,
+    /// How the connection to the SMTP server is secured
+    #[arg(long, env, value_enum, default_value_t = SmtpTls::Starttls)]
+    smtp_tls: SmtpTls,
+    /// Username to authenticate with the SMTP server
+    #[arg(long, env, requires = "smtp_password")]
+    smtp_username: Option<String>,
+    /// Password to authenticate with the SMTP server
+    #[arg(long, env, requires = "smtp_username")]
+    smtp_password: Option<String>,
+    /// Sender of emails (e.g. `k8s-notifier <notifier@example.com>`). Required if
+    /// 'email' is configured as a notifier
+    #[arg(long, env)]
+    email_from: Option<Mailbox>,
+    /// Recipients of all email notifications, separated by `,`
+    #[arg(long, env, value_delimiter = ',')]
+    email_to: Vec<Mailbox>,
+    /// Additional recipients of info email notifications, separated by `,`
+    #[arg(long, env, value_delimiter = ',')]
+    email_to_info: Vec<Mailbox>,
+    /// Additional recipients of warn email notifications, separated by `,`
+    #[arg(long, env, value_delimiter = ',')]
+    email_to_warn: Vec<Mailbox>,
+    /// Additional recipients of error email notifications, separated by `,`
+    #[arg(long, env, value_delimiter = ',')]
+    email_to_error: Vec<Mailbox>,
+    /// NATS server URL (e.g. `nats://nats:4222`). Required if 'nats' is configured as a
+    /// notifier
+    #[cfg(feature = "nats")]
+    #[arg(long, env)]
+    nats_url: Option<String>,
+    /// NATS subject prefix to publish notifications under. Notifications are published
+    /// to `<subject>.<namespace>.<name>`
+    #[cfg(feature = "nats")]
+    #[arg(long, env, default_value = "k8s-notifier")]
+    nats_subject: String,
+    /// Opsgenie API key. Required if 'opsgenie' is configured as a notifier
+    #[arg(long, env)]
+    opsgenie_api_key: Option<String>,
+    /// Opsgenie API base URL
+    #[arg(long, env, default_value = opsgenie::DEFAULT_API_URL)]
+    opsgenie_api_url: reqwest::Url,
+    /// The minimum level of notification which creates an Opsgenie alert, regardless
+    /// of the notifier log level
+    #[arg(long, env, default_value_t = NotifierLogLevel::Error)]
+    opsgenie_min_level: NotifierLogLevel,
+    /// PagerDuty Events API v2 integration key. Required if 'pagerduty' is configured
+    /// as a notifier
+    #[arg(long, env)]
+    pagerduty_routing_key: Option<String>,
+    /// PagerDuty Events API v2 endpoint
+    #[arg(long, env, default_value = pagerduty::DEFAULT_EVENTS_URL)]
+    pagerduty_events_url: String,
+    /// The minimum level of notification which triggers a PagerDuty incident,
+    /// regardless of the notifier log level
+    #[arg(long, env, default_value_t = NotifierLogLevel::Error)]
+    pagerduty_min_level: NotifierLogLevel,
+    /// Microsoft Teams incoming webhook URL. Required if 'teams' is configured as a
+    /// notifier
+    #[arg(long, env)]
+    teams_webhook_url: Option<reqwest::Url>,
+    /// URL to POST notifications to. Required if 'webhook' is configured as a notifier
+    #[arg(long, env)]
+    webhook_url: Option<reqwest::Url>,
+    /// How webhook request bodies are encoded. The CloudEvents formats send each
+    /// notification as a CloudEvents 1.0 event in structured or binary HTTP mode
+    #[arg(long, env, value_enum, default_value_t = WebhookFormat::Json)]
+    webhook_format: WebhookFormat,
+    /// Headers to send with each webhook request in the form `<name>: <value>`,
+    /// separated by `;`
+    #[arg(long, env, value_delimiter = ';')]
+    webhook_header: Vec<WebhookHeader>,
+    /// Bearer token to authenticate webhook requests with
+    #[arg(long, env, conflicts_with = "webhook_basic_auth")]
+    webhook_bearer_token: Option<String>,
+    /// Credentials to authenticate webhook requests with, in the form
+    /// `<username>:<password>`
+    #[arg(long, env)]
+    webhook_basic_auth: Option<String>,
+    /// Secret used to sign webhook request bodies. The HMAC-SHA256 signature is sent
+    /// in the `X-K8s-Notifier-Signature` header as `sha256=<hex digest>`
+    #[arg(long, env)]
+    webhook_signing_secret: Option<String>,
     /// Log level for all notifiers
     #[arg(long, env, default_value_t = NotifierLogLevel::Error)]
     notifier_log_level: NotifierLogLevel,
+    /// Output format of the 'log' notifier. With `json`, notifications are written to
+    /// stdout one object per line and application logs are written to stderr
+    #[arg(long, env, value_enum, default_value_t = LogFormat::Text)]
+    log_notifier_format: LogFormat,
     /// The name of the Kubernetes cluster we're running in. Used for logging
     /// purposes
     #[arg(long, env)]
     cluster_name: String,
+    /// How long, in seconds, a cron job may fall behind its schedule before a
+    /// notification is emitted
+    #[arg(long, env, default_value_t = 300)]
+    cronjob_grace_period: i64,
+    /// The number of lines of a failing container's logs to attach to its pod's
+    /// notifications. Set to 0 to disable fetching logs
+    #[arg(long, env, default_value_t = 20)]
+    container_log_lines: i64,
+    /// The maximum number of bytes of a failing container's logs to attach to its
+    /// pod's notifications. Older lines beyond this limit are dropped
+    #[arg(long, env, default_value_t = 2000)]
+    container_log_bytes: usize,
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let mut args = CliArgs::parse();
-    tracing_subscriber::fmt::init();
+
+    // Keep stdout free for the log notifier's JSON records
+    let json_log_notifier = args.log_notifier_format == LogFormat::Json
+        && args
+            .notifiers
+            .iter()
+            .any(|notifier| matches!(notifier, NotifierType::Log));
+    if json_log_notifier {
+        tracing_subscriber::fmt()
+            .with_writer(std::io::stderr)
+            .init();
+    } else {
+        tracing_subscriber::fmt::init();
+    }
 
     let client = Client::try_default().await?;
 
     let ns_scope = if args.all_namespaces {
-        NamespaceScope::All
+        NamespaceScope::All {
+            excluded: args.exclude_namespaces,
+        }
+    } else if let Some(selector) = args.namespace_selector {
+        NamespaceScope::Selector {
+            selector,
+            excluded: args.exclude_namespaces,
+        }
     } else {
         NamespaceScope::Names(args.namespaces.unwrap_or_default())
     };
 
     let mut handles = vec![];
-    let watcher = ResourceWatcher::new(client, ns_scope, args.resources);
+    let selectors = ResourceSelectors::new(args.label_selector, args.field_selector);
+    let container_logs = (args.container_log_lines > 0).then_some(ContainerLogOptions {
+        lines: args.container_log_lines,
+        max_bytes: args.container_log_bytes,
+    });
+    let watcher = ResourceWatcher::new(client, ns_scope, args.resources, selectors, container_logs);
 
-    let (handle, tx) = watcher.watch();
+    let tracker = StateTracker::new(chrono::Duration::seconds(args.cronjob_grace_period));
+    let (handle, tx) = watcher.watch(tracker).await?;
     handles.push(handle);
 
     for notifier in args.notifiers {
         let handle = match notifier {
+            NotifierType::Alertmanager => {
+                let alertmanager_notifier = AlertmanagerNotifier::new(
+                    tx.subscribe(),
+                    args.alertmanager_url.take().expect(
+                        "ALERTMANAGER_URL/--alertmanager-url must be set if the 'alertmanager' notifier is enabled",
+                    ),
+                    std::time::Duration::from_secs(args.alertmanager_resend_interval),
+                    args.alertmanager_min_level,
+                    args.cluster_name.clone(),
+                );
+
+                handles.push(alertmanager_notifier.resend_active_alerts());
+                alertmanager_notifier.run()
+            }
+            NotifierType::Discord => {
+                let discord_notifier = DiscordNotifier::new(
+                    tx.subscribe(),
+                    args.discord_webhook_url.take().expect(
+                        "DISCORD_WEBHOOK_URL/--discord-webhook-url must be set if the 'discord' notifier is enabled",
+                    ),
+                    args.notifier_log_level,
+                    args.cluster_name.clone(),
+                );
+
+                discord_notifier.run()
+            }
+            NotifierType::Email => {
+                let config = EmailConfig {
+                    smtp_host: args.smtp_host.take().expect(
+                        "SMTP_HOST/--smtp-host must be set if the 'email' notifier is enabled",
+                    ),
+                    smtp_port: args.smtp_port,
+                    tls: args.smtp_tls,
+                    username: args.smtp_username.take(),
+                    password: args.smtp_password.take(),
+                    from: args.email_from.take().expect(
+                        "EMAIL_FROM/--email-from must be set if the 'email' notifier is enabled",
+                    ),
+                    recipients: EmailRecipients {
+                        all: std::mem::take(&mut args.email_to),
+                        info: std::mem::take(&mut args.email_to_info),
+                        warn: std::mem::take(&mut args.email_to_warn),
+                        error: std::mem::take(&mut args.email_to_error),
+                    },
+                };
+
+                let email_notifier = EmailNotifier::new(
+                    tx.subscribe(),
+                    config,
+                    args.notifier_log_level,
+                    args.cluster_name.clone(),
+                )?;
+
+                email_notifier.run()
+            }
+            NotifierType::File => {
+                let config = FileConfig {
+                    path: args.file_path.take().expect(
+                        "FILE_PATH/--file-path must be set if the 'file' notifier is enabled",
+                    ),
+                    max_bytes: args.file_max_bytes,
+                    max_age: args.file_max_age.map(std::time::Duration::from_secs),
+                    max_files: args.file_max_files,
+                    compress: args.file_compress,
+                };
+
+                let file_notifier = FileNotifier::new(
+                    tx.subscribe(),
+                    config,
+                    args.notifier_log_level,
+                    args.cluster_name.clone(),
+                )?;
+
+                file_notifier.run()
+            }
+            #[cfg(feature = "kafka")]
+            NotifierType::Kafka => {
+                let kafka_notifier = KafkaNotifier::new(
+                    tx.subscribe(),
+                    args.kafka_brokers.take().expect(
+                        "KAFKA_BROKERS/--kafka-brokers must be set if the 'kafka' notifier is enabled",
+                    ),
+                    args.kafka_topic.take().expect(
+                        "KAFKA_TOPIC/--kafka-topic must be set if the 'kafka' notifier is enabled",
+                    ),
+                    std::mem::take(&mut args.kafka_config),
+                    args.notifier_log_level,
+                    args.cluster_name.clone(),
+                )?;
+
+                kafka_notifier.run()
+            }
             NotifierType::Log => {
-                let log_notifier = LogNotifier::new(tx.subscribe(), args.notifier_log_level);
+                let log_notifier = LogNotifier::new(
+                    tx.subscribe(),
+                    args.notifier_log_level,
+                    args.log_notifier_format,
+                    args.cluster_name.clone(),
+                );
 
                 log_notifier.run()
             }
+            NotifierType::Mattermost => {
+                let mattermost_notifier = MattermostNotifier::new(
+                    tx.subscribe(),
+                    args.mattermost_webhook_url.take().expect(
+                        "MATTERMOST_WEBHOOK_URL/--mattermost-webhook-url must be set if the 'mattermost' notifier is enabled",
+                    ),
+                    args.mattermost_channel.take(),
+                    args.notifier_log_level,
+                    args.cluster_name.clone(),
+                );
+
+                mattermost_notifier.run()
+            }
+            #[cfg(feature = "nats")]
+            NotifierType::Nats => {
+                let nats_notifier = NatsNotifier::new(
+                    tx.subscribe(),
+                    args.nats_url.take().expect(
+                        "NATS_URL/--nats-url must be set if the 'nats' notifier is enabled",
+                    ),
+                    args.nats_subject.clone(),
+                    args.notifier_log_level,
+                    args.cluster_name.clone(),
+                )
+                .await?;
+
+                nats_notifier.run()
+            }
+            NotifierType::Opsgenie => {
+                let opsgenie_notifier = OpsgenieNotifier::new(
+                    tx.subscribe(),
+                    args.opsgenie_api_key.take().expect(
+                        "OPSGENIE_API_KEY/--opsgenie-api-key must be set if the 'opsgenie' notifier is enabled",
+                    ),
+                    args.opsgenie_api_url.clone(),
+                    args.opsgenie_min_level,
+                    args.cluster_name.clone(),
+                );
+
+                opsgenie_notifier.run()
+            }
+            NotifierType::PagerDuty => {
+                let pagerduty_notifier = PagerDutyNotifier::new(
+                    tx.subscribe(),
+                    args.pagerduty_routing_key.take().expect(
+                        "PAGERDUTY_ROUTING_KEY/--pagerduty-routing-key must be set if the 'pagerduty' notifier is enabled",
+                    ),
+                    args.pagerduty_events_url.clone(),
+                    args.pagerduty_min_level,
+                    args.cluster_name.clone(),
+                );
+
+                pagerduty_notifier.run()
+            }
             NotifierType::Slack => {
+                let target = match (
+                    args.slack_token.take(),
+                    args.slack_channel.take(),
+                    args.slack_webhook_url.take(),
+                ) {
+                    (Some(token), Some(default_channel), None) => SlackTarget::Api {
+                        token,
+                        api_url: args.slack_api_url.clone(),
+                        default_channel,
+                        routes: std::mem::take(&mut args.slack_route),
+                    },
+                    (None, None, Some(url)) => SlackTarget::Webhook { url },
+                    _ => anyhow::bail!(
+                        "Exactly one of SLACK_TOKEN/--slack-token with SLACK_CHANNEL/--slack-channel, or SLACK_WEBHOOK_URL/--slack-webhook-url must be set if the 'slack' notifier is enabled"
+                    ),
+                };
+
                 let slack_notifier = SlackNotifier::new(
                     tx.subscribe(),
-                    args.slack_token.take().expect("SLACK_TOKEN/--slack-token must be set if the 'slack' notifier is enabled"),
-                    args.slack_channel.take().expect("SLACK_CHANNEL/--slack-channel must be set if the 'slack' notifier is enabled"),
+                    target,
                     args.notifier_log_level,
                     args.cluster_name.clone(),
                 );
 
                 slack_notifier.run()
             }
+            NotifierType::Teams => {
+                let teams_notifier = TeamsNotifier::new(
+                    tx.subscribe(),
+                    args.teams_webhook_url.take().expect(
+                        "TEAMS_WEBHOOK_URL/--teams-webhook-url must be set if the 'teams' notifier is enabled",
+                    ),
+                    args.notifier_log_level,
+                    args.cluster_name.clone(),
+                );
+
+                teams_notifier.run()
+            }
+            NotifierType::Webhook => {
+                let auth = if let Some(token) = args.webhook_bearer_token.take() {
+                    Some(WebhookAuth::Bearer(token))
+                } else {
+                    args.webhook_basic_auth.take().map(|credentials| {
+                        match credentials.split_once(':') {
+                            Some((username, password)) => WebhookAuth::Basic {
+                                username: username.to_string(),
+                                password: Some(password.to_string()),
+                            },
+                            None => WebhookAuth::Basic {
+                                username: credentials,
+                                password: None,
+                            },
+                        }
+                    })
+                };
+
+                let config = WebhookConfig {
+                    url: args.webhook_url.take().expect(
+                        "WEBHOOK_URL/--webhook-url must be set if the 'webhook' notifier is enabled",
+                    ),
+                    format: args.webhook_format,
+                    headers: std::mem::take(&mut args.webhook_header),
+                    auth,
+                    signing_secret: args.webhook_signing_secret.take(),
+                };
+
+                let webhook_notifier = WebhookNotifier::new(
+                    tx.subscribe(),
+                    config,
+                    args.notifier_log_level,
+                    args.cluster_name.clone(),
+                );
+
+                webhook_notifier.run()
+            }
         };
 
         handles.push(handle);
@@ -97,3 +555,20 @@ async fn main() -> anyhow::Result<()> {
 
     Ok(())
 }
+
+fn parse_namespace_selector(s: &str) -> Result<String, String> {
+    validate_label_selector(s)?;
+    Ok(s.to_string())
+}
+
+#[cfg(feature = "kafka")]
+fn parse_kafka_property(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!(
+            "invalid kafka property '{s}': expected the form <key>=<value>"
+        )),
+    }
+}
@@ -1,6 +1,9 @@
+pub mod logs;
 pub mod namespace;
 pub mod notifier;
 pub mod resource;
+pub mod selector;
+pub mod state;
 pub mod watcher;
 
 pub use notifier::slack::SlackNotifier;
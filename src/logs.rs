@@ -0,0 +1,327 @@
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::{Api, LogParams};
+use kube::Client;
+use tracing::warn;
+
+use crate::resource::ext::pod::{PodContainerStatus, PodExt};
+use crate::resource::{ChangeType, PackedResource};
+use crate::state::ResourceUpdate;
+
+/// Limits on how much of a container's logs are attached to a notification
+#[derive(Debug, Clone, Copy)]
+pub struct ContainerLogOptions {
+    /// The number of lines to fetch from the end of the logs
+    pub lines: i64,
+    /// The maximum number of bytes to keep from the end of the fetched lines
+    pub max_bytes: usize,
+}
+
+/// The most recent logs of a single container
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContainerLogs {
+    /// Name of the container the logs were read from
+    pub container: String,
+    /// Whether the logs are from the container's previous run, i.e. before it was
+    /// last restarted
+    pub previous: bool,
+    /// The log lines, oldest first
+    pub content: String,
+    /// Whether older lines were dropped to stay within [`ContainerLogOptions::max_bytes`]
+    pub truncated: bool,
+}
+
+/// Fetches the logs of containers which have started failing, so they can be attached
+/// to the notifications for their pods
+pub struct ContainerLogFetcher {
+    client: Client,
+    options: ContainerLogOptions,
+}
+
+impl ContainerLogFetcher {
+    pub fn new(client: Client, options: ContainerLogOptions) -> Self {
+        Self { client, options }
+    }
+
+    /// Whether any logs would be attached to `update`, i.e. whether it is for a pod
+    /// with containers which started failing since the pod was last observed
+    pub fn wants_logs(update: &ResourceUpdate) -> bool {
+        !Self::newly_failing_containers(update).is_empty()
+    }
+
+    /// Attaches logs to `update` for each container of a pod which started failing
+    /// since the pod was last observed. Failures to fetch logs are logged and
+    /// otherwise ignored, so that the notification is still sent
+    pub async fn attach(&self, update: &mut ResourceUpdate) {
+        let PackedResource::Pod(pod) = &update.resource else {
+            return;
+        };
+
+        let Some(namespace) = PodExt::namespace(pod) else {
+            return;
+        };
+
+        let api: Api<Pod> = Api::namespaced(self.client.clone(), &namespace);
+        let name = PodExt::name(pod);
+
+        for container in Self::newly_failing_containers(update) {
+            let previous = reads_previous_run(&container);
+            let params = LogParams {
+                container: Some(container.name.clone()),
+                previous,
+                tail_lines: Some(self.options.lines),
+                ..Default::default()
+            };
+
+            match api.logs(&name, &params).await {
+                Ok(content) => {
+                    let (content, truncated) = tail_bytes(content, self.options.max_bytes);
+                    update.logs.push(ContainerLogs {
+                        container: container.name,
+                        previous,
+                        content,
+                        truncated,
+                    });
+                }
+                Err(e) => warn!(
+                    "Failed to fetch logs of container {} in pod {namespace}/{name}: {e}",
+                    container.name
+                ),
+            }
+        }
+    }
+
+    /// The containers of the pod `update` is for which have run and started failing
+    /// since the pod was last observed
+    fn newly_failing_containers(update: &ResourceUpdate) -> Vec<PodContainerStatus> {
+        if update.change == ChangeType::Deleted {
+            return vec![];
+        }
+
+        let PackedResource::Pod(pod) = &update.resource else {
+            return vec![];
+        };
+
+        pod.failing_containers()
+            .into_iter()
+            .filter(|container| {
+                let previous_reason = update
+                    .previous
+                    .as_ref()
+                    .and_then(|state| state.failing_containers.get(&container.name));
+
+                // Containers which haven't run (e.g. those unable to pull their image)
+                // have no logs, and containers which were already failing had logs
+                // attached when they started to
+                let has_run = container.restart_count > 0 || container.terminated_reason.is_some();
+                has_run && previous_reason != container.failure_reason()
+            })
+            .collect()
+    }
+}
+
+/// Whether the logs of `container` should be read from its previous run. A container
+/// which has restarted and isn't currently terminated (e.g. one waiting in
+/// `CrashLoopBackOff`) failed during its previous run, so its current one has no
+/// useful logs
+fn reads_previous_run(container: &PodContainerStatus) -> bool {
+    container.restart_count > 0 && container.terminated_reason.is_none()
+}
+
+/// Keeps at most the last `max_bytes` bytes of `content`, starting from the first
+/// whole line within that limit
+fn tail_bytes(content: String, max_bytes: usize) -> (String, bool) {
+    if content.len() <= max_bytes {
+        return (content, false);
+    }
+
+    let mut start = content.len() - max_bytes;
+    while !content.is_char_boundary(start) {
+        start += 1;
+    }
+
+    // Skip the partial line the limit falls within, unless it falls on a line start
+    let tail = &content[start..];
+    let tail = match tail.find('\n') {
+        Some(i) if i + 1 < tail.len() && !content[..start].ends_with('\n') => &tail[i + 1..],
+        _ => tail,
+    };
+
+    (tail.to_string(), true)
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::api::core::v1::{
+        ContainerState, ContainerStateTerminated, ContainerStateWaiting, ContainerStatus, PodStatus,
+    };
+    use kube::api::ObjectMeta;
+
+    use super::*;
+    use crate::state::ResourceState;
+
+    #[test]
+    fn keeps_content_within_limit() {
+        let content = "first\nsecond\n".to_string();
+
+        assert_eq!(tail_bytes(content.clone(), 100), (content.clone(), false));
+        assert_eq!(tail_bytes(content.clone(), content.len()), (content, false));
+    }
+
+    #[test]
+    fn drops_partial_lines_when_truncating() {
+        let content = "first\nsecond\nthird\n".to_string();
+
+        assert_eq!(
+            tail_bytes(content.clone(), 12),
+            ("third\n".to_string(), true)
+        );
+        assert_eq!(
+            tail_bytes(content, 13),
+            ("second\nthird\n".to_string(), true)
+        );
+    }
+
+    #[test]
+    fn keeps_partial_line_without_earlier_newline() {
+        let content = "a very long line".to_string();
+
+        assert_eq!(tail_bytes(content, 9), ("long line".to_string(), true));
+    }
+
+    #[test]
+    fn truncates_on_char_boundaries() {
+        // Each "é" is 2 bytes, so a 5 byte limit starts mid-character
+        let content = "éééé".to_string();
+        assert_eq!(tail_bytes(content, 5), ("éé".to_string(), true));
+
+        let content = "old\n日本語\n".to_string();
+        let (tail, truncated) = tail_bytes(content, 9);
+        assert!(truncated);
+        assert_eq!(tail, "本語\n");
+    }
+
+    fn container(
+        restart_count: i32,
+        waiting: Option<&str>,
+        terminated: Option<&str>,
+    ) -> ContainerStatus {
+        let terminated_state = |reason: &str| ContainerStateTerminated {
+            reason: Some(reason.to_string()),
+            exit_code: 1,
+            ..Default::default()
+        };
+
+        ContainerStatus {
+            name: "app".to_string(),
+            restart_count,
+            state: Some(ContainerState {
+                waiting: waiting.map(|reason| ContainerStateWaiting {
+                    reason: Some(reason.to_string()),
+                    ..Default::default()
+                }),
+                terminated: terminated.map(terminated_state),
+                ..Default::default()
+            }),
+            last_state: (restart_count > 0).then(|| ContainerState {
+                terminated: Some(terminated_state("Error")),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn update(
+        change: ChangeType,
+        status: ContainerStatus,
+        previous_reason: Option<&str>,
+    ) -> ResourceUpdate {
+        let resource = PackedResource::Pod(Pod {
+            metadata: ObjectMeta {
+                name: Some("web".to_string()),
+                namespace: Some("default".to_string()),
+                ..Default::default()
+            },
+            status: Some(PodStatus {
+                container_statuses: Some(vec![status]),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+
+        let previous = ResourceState {
+            failing_containers: previous_reason
+                .map(|reason| ("app".to_string(), reason.to_string()))
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        };
+
+        ResourceUpdate {
+            change,
+            current: ResourceState::new(&resource, chrono::Duration::minutes(5)),
+            resource,
+            previous: Some(previous),
+            logs: vec![],
+        }
+    }
+
+    fn failing(update: &ResourceUpdate) -> Vec<(String, bool)> {
+        ContainerLogFetcher::newly_failing_containers(update)
+            .into_iter()
+            .map(|container| {
+                let previous = reads_previous_run(&container);
+                (container.name, previous)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reads_previous_run_of_restarted_containers() {
+        let crash_looping = container(3, Some("CrashLoopBackOff"), None);
+        let update = update(ChangeType::Applied, crash_looping, None);
+
+        assert_eq!(failing(&update), vec![("app".to_string(), true)]);
+    }
+
+    #[test]
+    fn reads_current_run_of_terminated_containers() {
+        let first_run = update(
+            ChangeType::Applied,
+            container(0, None, Some("OOMKilled")),
+            None,
+        );
+        assert_eq!(failing(&first_run), vec![("app".to_string(), false)]);
+
+        // A container terminated after restarting failed during its current run
+        let restarted = update(
+            ChangeType::Applied,
+            container(2, None, Some("OOMKilled")),
+            None,
+        );
+        assert_eq!(failing(&restarted), vec![("app".to_string(), false)]);
+    }
+
+    #[test]
+    fn skips_containers_which_never_ran() {
+        let pulling = container(0, Some("ImagePullBackOff"), None);
+        let update = update(ChangeType::Applied, pulling, None);
+
+        assert!(failing(&update).is_empty());
+    }
+
+    #[test]
+    fn skips_containers_already_failing_for_the_same_reason() {
+        let crash_looping = container(4, Some("CrashLoopBackOff"), None);
+        let update = update(ChangeType::Applied, crash_looping, Some("CrashLoopBackOff"));
+
+        assert!(failing(&update).is_empty());
+    }
+
+    #[test]
+    fn skips_deleted_pods() {
+        let crash_looping = container(3, Some("CrashLoopBackOff"), None);
+        let update = update(ChangeType::Deleted, crash_looping, None);
+
+        assert!(failing(&update).is_empty());
+    }
+}
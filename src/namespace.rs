@@ -1,5 +1,125 @@
+use std::str::FromStr;
+
 #[derive(Debug, Clone)]
 pub enum NamespaceScope {
-    All,
+    /// All namespaces, except those matching any of the excluded patterns
+    All {
+        excluded: Vec<NamespacePattern>,
+    },
     Names(Vec<String>),
+    /// Namespaces matching a label selector, e.g. `notify=true`, except those matching
+    /// any of the excluded patterns. Namespaces are watched as they're created or
+    /// labelled, so matching namespaces can be added without restarting
+    Selector {
+        selector: String,
+        excluded: Vec<NamespacePattern>,
+    },
+}
+
+impl NamespaceScope {
+    /// Whether resources in `namespace` should be ignored. An explicit list of names is
+    /// never subject to exclusions
+    pub fn excludes(&self, namespace: &str) -> bool {
+        match self {
+            Self::All { excluded } | Self::Selector { excluded, .. } => {
+                excluded.iter().any(|pattern| pattern.matches(namespace))
+            }
+            Self::Names(_) => false,
+        }
+    }
+
+    /// Whether resources in `namespace` should be watched. Cluster-scoped resources,
+    /// which have no namespace, are always included
+    pub fn includes(&self, namespace: Option<&str>) -> bool {
+        !namespace.is_some_and(|namespace| self.excludes(namespace))
+    }
+}
+
+/// A glob pattern matched against namespace names, e.g. `kube-*` or `ci-sandbox-?`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NamespacePattern(glob::Pattern);
+
+impl NamespacePattern {
+    pub fn matches(&self, namespace: &str) -> bool {
+        self.0.matches(namespace)
+    }
+}
+
+impl FromStr for NamespacePattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        glob::Pattern::new(s)
+            .map(Self)
+            .map_err(|e| format!("invalid namespace pattern '{s}': {e}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patterns(patterns: &[&str]) -> Vec<NamespacePattern> {
+        patterns.iter().map(|p| p.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn matches_glob_patterns() {
+        let pattern: NamespacePattern = "kube-*".parse().unwrap();
+        assert!(pattern.matches("kube-system"));
+        assert!(!pattern.matches("default"));
+
+        let pattern: NamespacePattern = "ci-sandbox-?".parse().unwrap();
+        assert!(pattern.matches("ci-sandbox-1"));
+        assert!(!pattern.matches("ci-sandbox-12"));
+
+        assert!("[".parse::<NamespacePattern>().is_err());
+    }
+
+    #[test]
+    fn excludes_matching_namespaces() {
+        let excluded = patterns(&["kube-*", "cert-manager"]);
+        let scopes = [
+            NamespaceScope::All {
+                excluded: excluded.clone(),
+            },
+            NamespaceScope::Selector {
+                selector: "notify=true".to_string(),
+                excluded,
+            },
+        ];
+
+        for scope in scopes {
+            assert!(scope.excludes("kube-system"), "{scope:?}");
+            assert!(scope.excludes("cert-manager"), "{scope:?}");
+            assert!(!scope.excludes("cert-manager-webhook"), "{scope:?}");
+            assert!(!scope.excludes("payments"), "{scope:?}");
+        }
+    }
+
+    #[test]
+    fn never_excludes_named_namespaces() {
+        let scope = NamespaceScope::Names(vec!["kube-system".to_string()]);
+
+        assert!(!scope.excludes("kube-system"));
+        assert!(scope.includes(Some("kube-system")));
+    }
+
+    #[test]
+    fn includes_everything_without_exclusions() {
+        let scope = NamespaceScope::All { excluded: vec![] };
+
+        assert!(!scope.excludes("kube-system"));
+        assert!(scope.includes(Some("default")));
+    }
+
+    #[test]
+    fn always_includes_cluster_scoped_resources() {
+        let scope = NamespaceScope::All {
+            excluded: patterns(&["*"]),
+        };
+
+        assert!(!scope.includes(Some("default")));
+        assert!(scope.includes(None));
+    }
 }
@@ -0,0 +1,268 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::content::NotificationContent;
+use super::NotifierLogLevel;
+
+use crate::resource::ChangeType;
+use crate::state::ResourceUpdate;
+
+/// A change to an alert in an incident management tool
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AlertAction {
+    /// Opens the alert identified by `key`
+    Trigger { key: String },
+    /// Closes the previously triggered alert identified by `key`
+    Resolve { key: String },
+}
+
+/// Remembers the alert currently open for each resource, so that notifiers for
+/// incident management tools can resolve alerts once their resource recovers.
+///
+/// Alerts are keyed by the resource's UID and the reason for the alert (e.g.
+/// `<uid>:CrashLoopBackOff`), so that the same problem is deduplicated by the tool
+/// when it is triggered again, while a different problem opens a new alert.
+///
+/// Open alerts are only remembered in memory. Since keys are deterministic, an alert
+/// opened before the notifier restarted is picked up again if its resource is still
+/// failing for the same reason when it is first listed, and then resolved as usual.
+/// Alerts whose resource recovered while the notifier wasn't running are left open.
+///
+/// Notifications resolving an alert carry the notifier's minimum level rather than
+/// the level of the update which resolved it, so that they're never filtered out
+#[derive(Debug, Default)]
+pub struct AlertTracker {
+    /// The key of the alert open for each observed resource, if there is one
+    resources: Mutex<HashMap<String, Option<String>>>,
+}
+
+impl AlertTracker {
+    /// Determines which alerts to trigger or resolve in response to `update`. An alert
+    /// is triggered when the content is at least as severe as `min_level`, and resolved
+    /// when the resource becomes healthy (i.e. its content is informational), is
+    /// deleted, or starts failing for a different reason. Informational content never
+    /// triggers an alert, whatever `min_level` is
+    pub fn observe(
+        &self,
+        update: &ResourceUpdate,
+        content: Option<&NotificationContent>,
+        min_level: NotifierLogLevel,
+    ) -> Vec<AlertAction> {
+        let resource = &update.resource;
+        let id = resource.uid().unwrap_or_else(|| {
+            format!(
+                "{}/{}/{}",
+                resource.kind(),
+                resource.namespace().unwrap_or_default(),
+                resource.name()
+            )
+        });
+
+        let mut resources = self.resources.lock().expect("alert tracker lock poisoned");
+        let open = resources.entry(id.clone()).or_default();
+        let mut actions = vec![];
+
+        let deleted = update.change == ChangeType::Deleted;
+        let healthy = content.is_some_and(|c| c.level == NotifierLogLevel::Info);
+
+        match content {
+            Some(content) if !deleted && !healthy && content.level >= min_level => {
+                let key = alert_key(&id, content.reason.as_deref());
+
+                match open.replace(key.clone()) {
+                    Some(previous) if previous == key => {}
+                    Some(previous) => {
+                        actions.push(AlertAction::Resolve { key: previous });
+                        actions.push(AlertAction::Trigger { key });
+                    }
+                    None => actions.push(AlertAction::Trigger { key }),
+                }
+            }
+            _ if deleted || healthy => {
+                if let Some(key) = open.take() {
+                    actions.push(AlertAction::Resolve { key });
+                }
+
+                if deleted {
+                    resources.remove(&id);
+                }
+            }
+            _ => {}
+        }
+
+        actions
+    }
+}
+
+fn alert_key(id: &str, reason: Option<&str>) -> String {
+    format!("{id}:{}", reason.unwrap_or("Unhealthy"))
+}
+
+/// The severity of an alert for content of `level`, as named by PagerDuty and
+/// Alertmanager
+pub(crate) fn get_severity(level: NotifierLogLevel) -> &'static str {
+    match level {
+        NotifierLogLevel::Info => "info",
+        NotifierLogLevel::Warn => "warning",
+        NotifierLogLevel::Error => "critical",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::api::core::v1::Pod;
+    use kube::api::ObjectMeta;
+
+    use super::*;
+    use crate::resource::PackedResource;
+    use crate::state::ResourceState;
+
+    fn update(change: ChangeType) -> ResourceUpdate {
+        ResourceUpdate {
+            change,
+            resource: PackedResource::Pod(Pod {
+                metadata: ObjectMeta {
+                    name: Some("web-0".to_string()),
+                    namespace: Some("default".to_string()),
+                    uid: Some("uid".to_string()),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }),
+            previous: Some(ResourceState::default()),
+            current: ResourceState::default(),
+            logs: vec![],
+        }
+    }
+
+    fn content(level: NotifierLogLevel, reason: Option<&str>) -> NotificationContent {
+        NotificationContent::new("Pod `web-0` changed".to_string(), level, vec![])
+            .with_reason(reason)
+    }
+
+    fn trigger(key: &str) -> AlertAction {
+        AlertAction::Trigger {
+            key: key.to_string(),
+        }
+    }
+
+    fn resolve(key: &str) -> AlertAction {
+        AlertAction::Resolve {
+            key: key.to_string(),
+        }
+    }
+
+    #[test]
+    fn triggers_once_and_resolves_when_healthy() {
+        let alerts = AlertTracker::default();
+        let failing = content(NotifierLogLevel::Error, Some("CrashLoopBackOff"));
+        let healthy = content(NotifierLogLevel::Info, None);
+        let applied = update(ChangeType::Applied);
+
+        assert_eq!(
+            alerts.observe(&applied, Some(&failing), NotifierLogLevel::Error),
+            vec![trigger("uid:CrashLoopBackOff")]
+        );
+        assert_eq!(
+            alerts.observe(&applied, Some(&failing), NotifierLogLevel::Error),
+            vec![]
+        );
+        assert_eq!(
+            alerts.observe(&applied, Some(&healthy), NotifierLogLevel::Error),
+            vec![resolve("uid:CrashLoopBackOff")]
+        );
+        assert_eq!(
+            alerts.observe(&applied, Some(&healthy), NotifierLogLevel::Error),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn replaces_alert_when_reason_changes() {
+        let alerts = AlertTracker::default();
+        let applied = update(ChangeType::Applied);
+
+        alerts.observe(
+            &applied,
+            Some(&content(NotifierLogLevel::Error, Some("OOMKilled"))),
+            NotifierLogLevel::Error,
+        );
+
+        assert_eq!(
+            alerts.observe(
+                &applied,
+                Some(&content(NotifierLogLevel::Error, Some("CrashLoopBackOff"))),
+                NotifierLogLevel::Error,
+            ),
+            vec![resolve("uid:OOMKilled"), trigger("uid:CrashLoopBackOff")]
+        );
+    }
+
+    #[test]
+    fn resolves_when_deleted() {
+        let alerts = AlertTracker::default();
+        let failing = content(NotifierLogLevel::Error, None);
+
+        alerts.observe(
+            &update(ChangeType::Applied),
+            Some(&failing),
+            NotifierLogLevel::Error,
+        );
+
+        assert_eq!(
+            alerts.observe(
+                &update(ChangeType::Deleted),
+                Some(&failing),
+                NotifierLogLevel::Error
+            ),
+            vec![resolve("uid:Unhealthy")]
+        );
+        assert!(alerts.resources.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn only_triggers_at_min_level() {
+        let alerts = AlertTracker::default();
+        let applied = update(ChangeType::Applied);
+        let warning = content(NotifierLogLevel::Warn, Some("Progressing"));
+
+        assert_eq!(
+            alerts.observe(&applied, Some(&warning), NotifierLogLevel::Error),
+            vec![]
+        );
+        assert_eq!(
+            alerts.observe(&applied, Some(&warning), NotifierLogLevel::Warn),
+            vec![trigger("uid:Progressing")]
+        );
+    }
+
+    #[test]
+    fn never_triggers_on_informational_content() {
+        let alerts = AlertTracker::default();
+        let healthy = content(NotifierLogLevel::Info, Some("Running"));
+
+        assert_eq!(
+            alerts.observe(
+                &update(ChangeType::Applied),
+                Some(&healthy),
+                NotifierLogLevel::Info
+            ),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn resolves_nothing_for_unknown_resources() {
+        let alerts = AlertTracker::default();
+        let healthy = content(NotifierLogLevel::Info, None);
+
+        assert_eq!(
+            alerts.observe(
+                &update(ChangeType::Applied),
+                Some(&healthy),
+                NotifierLogLevel::Error
+            ),
+            vec![]
+        );
+    }
+}
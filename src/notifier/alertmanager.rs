@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::json;
+use tokio::sync::broadcast;
+use tokio::time::MissedTickBehavior;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+
+use super::alert::{get_severity, AlertAction, AlertTracker};
+use super::content::{ContentBuilder, NotificationContent};
+use super::{impl_loggable, impl_resource_update_stream, Loggable, Notifier, NotifierLogLevel};
+
+use crate::state::ResourceUpdate;
+
+/// Notifier which posts alerts to Prometheus Alertmanager, so that they're subject to
+/// its routing, silences and inhibitions. Alerts are resolved by setting `endsAt` once
+/// the resource recovers
+pub struct AlertmanagerNotifier {
+    rx: BroadcastStream<ResourceUpdate>,
+    alerts_url: reqwest::Url,
+    client: reqwest::Client,
+    /// The minimum level of content which triggers an alert
+    min_level: NotifierLogLevel,
+    content_builder: ContentBuilder,
+    alerts: AlertTracker,
+    /// Alerts which haven't been resolved yet, by key
+    active: Arc<Mutex<HashMap<String, serde_json::Value>>>,
+    resend_interval: Duration,
+}
+
+impl AlertmanagerNotifier {
+    /// Creates a notifier posting to the Alertmanager at `url`. Active alerts are
+    /// re-sent every `resend_interval` by [`AlertmanagerNotifier::resend_active_alerts`]
+    pub fn new(
+        rx: broadcast::Receiver<ResourceUpdate>,
+        mut url: reqwest::Url,
+        resend_interval: Duration,
+        min_level: NotifierLogLevel,
+        cluster_name: String,
+    ) -> Self {
+        url.path_segments_mut()
+            .expect("Alertmanager URL should be an http(s) URL")
+            .pop_if_empty()
+            .extend(["api", "v2", "alerts"]);
+
+        Self {
+            rx: BroadcastStream::new(rx),
+            alerts_url: url,
+            client: reqwest::Client::new(),
+            min_level,
+            content_builder: ContentBuilder::new(cluster_name),
+            alerts: AlertTracker::default(),
+            active: Arc::default(),
+            resend_interval,
+        }
+    }
+
+    /// Spawns a task which periodically re-sends active alerts, so that Alertmanager
+    /// doesn't consider them resolved once its `resolve_timeout` elapses
+    pub fn resend_active_alerts(&self) -> tokio::task::JoinHandle<()> {
+        let client = self.client.clone();
+        let alerts_url = self.alerts_url.clone();
+        let active = self.active.clone();
+        let mut interval = tokio::time::interval(self.resend_interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+        tokio::spawn(async move {
+            // The first tick completes immediately, when there's nothing to re-send
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+
+                let alerts = active
+                    .lock()
+                    .expect("active alerts lock poisoned")
+                    .values()
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                if alerts.is_empty() {
+                    continue;
+                }
+
+                if let Err(e) = post_alerts(&client, &alerts_url, &alerts).await {
+                    tracing::error!("Failed to re-send active alertmanager alerts. Error: {e:?}");
+                }
+            }
+        })
+    }
+
+    fn trigger_alert(
+        &self,
+        key: String,
+        update: &ResourceUpdate,
+        content: &NotificationContent,
+    ) -> AlertmanagerNotification {
+        let resource = &update.resource;
+        let reason = content.reason.as_deref().unwrap_or("Unhealthy");
+
+        let mut labels = serde_json::Map::new();
+        labels.insert("alertname".to_string(), json!(reason));
+        labels.insert(
+            "cluster".to_string(),
+            json!(self.content_builder.cluster_name()),
+        );
+        if let Some(namespace) = resource.namespace() {
+            labels.insert("namespace".to_string(), json!(namespace));
+        }
+        labels.insert("kind".to_string(), json!(resource.kind()));
+        labels.insert("name".to_string(), json!(resource.name()));
+        labels.insert("reason".to_string(), json!(reason));
+        labels.insert("severity".to_string(), json!(get_severity(content.level)));
+
+        // Annotations only accept string values
+        let description = content
+            .fields()
+            .into_iter()
+            .map(|field| format!("{}: {}", field.name, field.value.to_plain_string()))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let alert = json!({
+            "labels": labels,
+            "annotations": {
+                "summary": content.plain_title(),
+                "description": description,
+            },
+            "startsAt": chrono::Utc::now().to_rfc3339(),
+        });
+
+        self.active
+            .lock()
+            .expect("active alerts lock poisoned")
+            .insert(key, alert.clone());
+
+        AlertmanagerNotification {
+            alert,
+            level: content.level,
+        }
+    }
+
+    /// Sets `endsAt` on the active alert identified by `key`. [`None`] if it isn't
+    /// active
+    fn resolve_alert(&self, key: String) -> Option<AlertmanagerNotification> {
+        let mut alert = self
+            .active
+            .lock()
+            .expect("active alerts lock poisoned")
+            .remove(&key)?;
+
+        alert["endsAt"] = json!(chrono::Utc::now().to_rfc3339());
+
+        Some(AlertmanagerNotification {
+            alert,
+            level: self.min_level,
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for AlertmanagerNotifier {
+    type Notification = AlertmanagerNotification;
+
+    fn create_notifications(&self, update: &ResourceUpdate) -> Vec<Self::Notification> {
+        let content = self.content_builder.build(update);
+
+        self.alerts
+            .observe(update, content.as_ref(), self.min_level)
+            .into_iter()
+            .filter_map(|action| match action {
+                AlertAction::Trigger { key } => content
+                    .as_ref()
+                    .map(|content| self.trigger_alert(key, update, content)),
+                AlertAction::Resolve { key } => self.resolve_alert(key),
+            })
+            .collect()
+    }
+
+    fn log_level(&self) -> NotifierLogLevel {
+        self.min_level
+    }
+
+    async fn emit_notification(&self, notification: Self::Notification) -> anyhow::Result<()> {
+        post_alerts(&self.client, &self.alerts_url, &[notification.alert]).await
+    }
+}
+
+impl_resource_update_stream!(AlertmanagerNotifier, rx);
+
+pub struct AlertmanagerNotification {
+    level: NotifierLogLevel,
+    alert: serde_json::Value,
+}
+
+impl_loggable!(AlertmanagerNotification, level);
+
+async fn post_alerts(
+    client: &reqwest::Client,
+    alerts_url: &reqwest::Url,
+    alerts: &[serde_json::Value],
+) -> anyhow::Result<()> {
+    let res = client.post(alerts_url.clone()).json(alerts).send().await?;
+
+    tracing::info!(
+        "Received status {} upon emitting alertmanager alerts",
+        res.status()
+    );
+
+    res.error_for_status()?;
+
+    Ok(())
+}
@@ -0,0 +1,201 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use super::content::NotificationRecord;
+
+use crate::resource::ChangeType;
+use crate::state::ResourceUpdate;
+
+/// The CloudEvents specification version events conform to
+pub const SPEC_VERSION: &str = "1.0";
+
+/// Prefix of every event's `type`, followed by the lowercased kind and the event
+const TYPE_PREFIX: &str = "io.k8s-notifier";
+
+/// Content type of a structured mode request, in which the whole event is the body
+pub const STRUCTURED_CONTENT_TYPE: &str = "application/cloudevents+json";
+
+/// A CloudEvents 1.0 event carrying a [`NotificationRecord`] as its data
+#[derive(Debug, Clone, Serialize)]
+pub struct CloudEvent {
+    pub specversion: &'static str,
+    pub id: String,
+    /// The name of the cluster the notification came from
+    pub source: String,
+    /// e.g. `io.k8s-notifier.pod.phase-changed`
+    #[serde(rename = "type")]
+    pub typ: String,
+    /// The object the notification describes, as `<namespace>/<name>` or `<name>`
+    pub subject: String,
+    pub time: DateTime<Utc>,
+    pub datacontenttype: &'static str,
+    pub data: NotificationRecord,
+}
+
+impl CloudEvent {
+    pub fn new(update: &ResourceUpdate, record: NotificationRecord) -> Self {
+        let id = format!(
+            "{}:{}{:09}",
+            record.uid.clone().unwrap_or_else(|| record.key()),
+            record.timestamp.timestamp(),
+            record.timestamp.timestamp_subsec_nanos()
+        );
+
+        Self {
+            specversion: SPEC_VERSION,
+            id,
+            source: record.cluster.clone(),
+            typ: format!(
+                "{TYPE_PREFIX}.{}.{}",
+                record.kind.to_lowercase(),
+                event_name(update)
+            ),
+            subject: record.key(),
+            time: record.timestamp,
+            datacontenttype: "application/json",
+            data: record,
+        }
+    }
+
+    /// The event's attributes as binary mode HTTP headers, e.g. `ce-type`. The data is
+    /// sent as the body, with the `datacontenttype` as its content type
+    pub fn binary_headers(&self) -> Vec<(String, String)> {
+        [
+            ("specversion", self.specversion.to_string()),
+            ("id", self.id.clone()),
+            ("source", self.source.clone()),
+            ("type", self.typ.clone()),
+            ("subject", self.subject.clone()),
+            ("time", self.time.to_rfc3339()),
+        ]
+        .into_iter()
+        .map(|(attribute, value)| (format!("ce-{attribute}"), value))
+        .collect()
+    }
+}
+
+/// Describes what happened to the resource, based on the first field that changed
+fn event_name(update: &ResourceUpdate) -> &'static str {
+    if update.change == ChangeType::Deleted {
+        return "deleted";
+    }
+
+    if update.previous.is_none() {
+        return "added";
+    }
+
+    match update.changes().first().map(|change| change.field.as_str()) {
+        Some("Phase") => "phase-changed",
+        Some("Schedulable") => "schedulability-changed",
+        Some("Count") => "recurred",
+        Some(field) if field.starts_with("Container ") => "container-status-changed",
+        Some(_) => "condition-changed",
+        None => "updated",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::api::core::v1::Pod;
+
+    use super::*;
+    use crate::resource::PackedResource;
+    use crate::state::ResourceState;
+
+    fn update(
+        change: ChangeType,
+        previous: Option<ResourceState>,
+        current: ResourceState,
+    ) -> ResourceUpdate {
+        ResourceUpdate {
+            change,
+            resource: PackedResource::Pod(Pod::default()),
+            previous,
+            current,
+            logs: vec![],
+        }
+    }
+
+    fn phase(phase: &str) -> ResourceState {
+        ResourceState {
+            phase: Some(phase.to_string()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn names_additions_and_deletions() {
+        let added = update(ChangeType::Applied, None, phase("Running"));
+        assert_eq!(event_name(&added), "added");
+
+        let deleted = update(
+            ChangeType::Deleted,
+            Some(phase("Running")),
+            phase("Running"),
+        );
+        assert_eq!(event_name(&deleted), "deleted");
+    }
+
+    #[test]
+    fn names_changes_by_first_changed_field() {
+        let phase_changed = update(
+            ChangeType::Applied,
+            Some(phase("Pending")),
+            phase("Running"),
+        );
+        assert_eq!(event_name(&phase_changed), "phase-changed");
+
+        let schedulability_changed = update(
+            ChangeType::Applied,
+            Some(ResourceState {
+                schedulable: Some(true),
+                ..Default::default()
+            }),
+            ResourceState {
+                schedulable: Some(false),
+                ..Default::default()
+            },
+        );
+        assert_eq!(
+            event_name(&schedulability_changed),
+            "schedulability-changed"
+        );
+
+        let recurred = update(
+            ChangeType::Applied,
+            Some(ResourceState {
+                count: Some(1),
+                ..Default::default()
+            }),
+            ResourceState {
+                count: Some(2),
+                ..Default::default()
+            },
+        );
+        assert_eq!(event_name(&recurred), "recurred");
+
+        let mut crash_looping = phase("Running");
+        crash_looping
+            .failing_containers
+            .insert("app".to_string(), "CrashLoopBackOff".to_string());
+        let container_changed = update(ChangeType::Applied, Some(phase("Running")), crash_looping);
+        assert_eq!(event_name(&container_changed), "container-status-changed");
+
+        let mut not_ready = phase("Running");
+        not_ready
+            .conditions
+            .insert("Ready".to_string(), "False".to_string());
+        let condition_changed = update(ChangeType::Applied, Some(phase("Running")), not_ready);
+        assert_eq!(event_name(&condition_changed), "condition-changed");
+    }
+
+    #[test]
+    fn names_relists_without_changes_as_updates() {
+        let relisted = update(
+            ChangeType::Restarted,
+            Some(phase("Running")),
+            phase("Running"),
+        );
+        assert_eq!(event_name(&relisted), "updated");
+    }
+}
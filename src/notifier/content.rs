@@ -0,0 +1,764 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+use chrono::{DateTime, Utc};
+use kube::api::ApiResource;
+use serde::{Serialize, Serializer};
+
+use super::NotifierLogLevel;
+
+use crate::logs::ContainerLogs;
+use crate::resource::ext::cronjob::CronJobExt;
+use crate::resource::ext::daemonset::DaemonSetExt;
+use crate::resource::ext::deployment::DeploymentExt;
+use crate::resource::ext::dynamic::DynamicObjectExt;
+use crate::resource::ext::event::EventExt;
+use crate::resource::ext::job::JobExt;
+use crate::resource::ext::node::NodeExt;
+use crate::resource::ext::pod::{PodContainerStatus, PodExt};
+use crate::resource::ext::rollout::RolloutStatus;
+use crate::resource::ext::statefulset::StatefulSetExt;
+use crate::resource::{ChangeType, PackedResource};
+use crate::state::{ResourceState, ResourceUpdate, StateChange};
+
+/// A single named value within a notification, e.g. a pod's namespace or labels
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Field {
+    pub name: String,
+    pub value: FieldValue,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(untagged)]
+pub enum FieldValue {
+    /// An identifier or other short value, rendered as inline code where possible
+    Code(String),
+    /// Free-form text, e.g. a message
+    Text(String),
+    /// Key value pairs in display order, e.g. labels or conditions
+    Entries(#[serde(serialize_with = "serialize_entries")] Vec<(String, String)>),
+}
+
+impl Field {
+    pub fn code(name: &str, value: impl Display) -> Self {
+        Self {
+            name: name.to_string(),
+            value: FieldValue::Code(value.to_string()),
+        }
+    }
+
+    pub fn text(name: &str, value: impl Display) -> Self {
+        Self {
+            name: name.to_string(),
+            value: FieldValue::Text(value.to_string()),
+        }
+    }
+
+    pub fn entries<K: Display, V: Display>(
+        name: &str,
+        entries: impl IntoIterator<Item = (K, V)>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            value: FieldValue::Entries(
+                entries
+                    .into_iter()
+                    .map(|(key, val)| (key.to_string(), val.to_string()))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl FieldValue {
+    /// Formats the value as markdown, in which values are rendered as inline code and
+    /// entries as a bulleted list. Empty values are rendered as `<None>`, since some
+    /// mediums reject empty fields
+    pub fn to_markdown(&self) -> String {
+        match self {
+            Self::Code(value) => format!("`{value}`"),
+            Self::Text(value) if value.is_empty() => "<None>".to_string(),
+            Self::Text(value) => value.clone(),
+            Self::Entries(entries) if entries.is_empty() => "<None>".to_string(),
+            Self::Entries(entries) => entries
+                .iter()
+                .map(|(key, val)| format!("• `{key}` : `{val}`"))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// Formats the value as plain text, for mediums which only accept string values.
+    /// Entries are rendered as a JSON object
+    pub fn to_plain_string(&self) -> String {
+        match self {
+            Self::Code(value) | Self::Text(value) => value.clone(),
+            Self::Entries(_) => serde_json::to_string(self).unwrap_or_default(),
+        }
+    }
+}
+
+fn serialize_entries<S: Serializer>(
+    entries: &[(String, String)],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(entries.iter().map(|(key, val)| (key, val)))
+}
+
+/// The content of a notification, independent of the medium it is sent on
+#[derive(Debug, Clone)]
+pub struct NotificationContent {
+    /// A one line summary. Values are wrapped in backticks and key phrases in
+    /// asterisks, as in Slack's mrkdwn, e.g. "Pod `web-0` is in phase *Failed*"
+    pub title: String,
+    pub level: NotifierLogLevel,
+    /// A short, machine readable cause of the notification, e.g. `CrashLoopBackOff`
+    /// or `Unschedulable`. [`None`] when the resource is healthy
+    pub reason: Option<String>,
+    /// Rows of fields, laid out side by side where the medium supports it
+    pub rows: Vec<Vec<Field>>,
+    /// What changed since the resource was last observed, if anything
+    pub changes: Vec<StateChange>,
+    /// Recent logs of failing containers, if any were fetched
+    pub logs: Vec<ContainerLogs>,
+}
+
+impl NotificationContent {
+    pub fn new(title: String, level: NotifierLogLevel, rows: Vec<Vec<Field>>) -> Self {
+        Self {
+            title,
+            level,
+            reason: None,
+            rows,
+            changes: vec![],
+            logs: vec![],
+        }
+    }
+
+    pub fn with_reason(mut self, reason: Option<impl Into<String>>) -> Self {
+        self.reason = reason.map(Into::into);
+        self
+    }
+
+    /// The title without any formatting
+    pub fn plain_title(&self) -> String {
+        self.title.replace(['*', '`'], "")
+    }
+
+    /// The title formatted as CommonMark, in which bold text uses double asterisks
+    pub fn markdown_title(&self) -> String {
+        self.title.replace('*', "**")
+    }
+
+    /// All fields of the notification in a single list, followed by fields describing
+    /// what changed and any attached logs. Used by notifiers which output structured
+    /// data rather than laying fields out visually
+    pub fn fields(&self) -> Vec<Field> {
+        let mut fields = self.rows.iter().flatten().cloned().collect::<Vec<_>>();
+
+        if !self.changes.is_empty() {
+            fields.push(Field::entries(
+                "Changes",
+                self.changes.iter().map(|change| {
+                    (
+                        change.field.as_str(),
+                        format!(
+                            "{} → {}",
+                            change.previous.as_deref().unwrap_or("<None>"),
+                            change.current.as_deref().unwrap_or("<None>")
+                        ),
+                    )
+                }),
+            ));
+        }
+
+        fields.extend(
+            self.logs
+                .iter()
+                .map(|logs| Field::text(&format!("Logs ({})", logs.container), &logs.content)),
+        );
+
+        fields
+    }
+}
+
+/// A stable, serializable description of a notification, for notifiers which output
+/// structured data (e.g. webhooks) rather than formatted messages
+#[derive(Debug, Clone, Serialize)]
+pub struct NotificationRecord {
+    pub timestamp: DateTime<Utc>,
+    pub cluster: String,
+    pub kind: String,
+    pub namespace: Option<String>,
+    pub name: String,
+    pub uid: Option<String>,
+    /// How the resource changed, one of `applied`, `deleted` or `restarted`
+    pub change: String,
+    pub level: NotifierLogLevel,
+    /// The title without any formatting
+    pub title: String,
+    pub fields: Vec<Field>,
+    /// The resource as observed, in the form returned by the API server
+    pub object: serde_json::Value,
+}
+
+impl NotificationRecord {
+    pub fn new(cluster: &str, update: &ResourceUpdate, content: &NotificationContent) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            cluster: cluster.to_string(),
+            kind: update.resource.kind(),
+            namespace: update.resource.namespace(),
+            name: update.resource.name(),
+            uid: update.resource.uid(),
+            change: update.change.to_string(),
+            level: content.level,
+            title: content.plain_title(),
+            fields: content.fields(),
+            object: update.resource.to_json(),
+        }
+    }
+
+    /// Identifies the object the record describes, as `<namespace>/<name>` or just
+    /// `<name>` for cluster scoped objects. Used by message brokers to keep the
+    /// records of each object in order
+    pub fn key(&self) -> String {
+        match &self.namespace {
+            Some(namespace) => format!("{namespace}/{}", self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+/// Builds the content of notifications for resource updates. Shared by all notifiers
+/// so that every medium describes a resource the same way
+#[derive(Debug, Clone)]
+pub struct ContentBuilder {
+    cluster_name: String,
+}
+
+impl ContentBuilder {
+    pub fn new(cluster_name: String) -> Self {
+        Self { cluster_name }
+    }
+
+    /// The name of the cluster notifications are built for
+    pub fn cluster_name(&self) -> &str {
+        &self.cluster_name
+    }
+
+    /// Builds the content describing `update`, or [`None`] if there is nothing worth
+    /// notifying about
+    pub fn build(&self, update: &ResourceUpdate) -> Option<NotificationContent> {
+        if update.change == ChangeType::Deleted {
+            return self.deleted_content(&update.resource);
+        }
+
+        let mut content = match &update.resource {
+            PackedResource::Node(node) => Some(self.node_content(node)),
+            PackedResource::Pod(pod) => self.pod_content(pod),
+            PackedResource::Event(event) => self.event_content(event),
+            PackedResource::Deployment(deployment) => Some(self.rollout_content(
+                "Deployment",
+                deployment.name(),
+                deployment.namespace(),
+                deployment.labels(),
+                deployment.status_conditions().unwrap_or_default(),
+                deployment.rollout_status(),
+            )),
+            PackedResource::StatefulSet(stateful_set) => Some(self.rollout_content(
+                "StatefulSet",
+                stateful_set.name(),
+                stateful_set.namespace(),
+                stateful_set.labels(),
+                stateful_set.status_conditions().unwrap_or_default(),
+                stateful_set.rollout_status(),
+            )),
+            PackedResource::DaemonSet(daemon_set) => Some(self.rollout_content(
+                "DaemonSet",
+                daemon_set.name(),
+                daemon_set.namespace(),
+                daemon_set.labels(),
+                daemon_set.status_conditions().unwrap_or_default(),
+                daemon_set.rollout_status(),
+            )),
+            PackedResource::Job(job) => Some(self.job_content(job)),
+            PackedResource::CronJob(cron_job) => {
+                Some(self.cronjob_content(cron_job, &update.current))
+            }
+            PackedResource::Dynamic(obj, api_resource) => {
+                Some(self.dynamic_content(obj, api_resource))
+            }
+        }?;
+
+        content.changes = update.changes();
+        content.logs = update.logs.clone();
+
+        Some(content)
+    }
+
+    fn node_content(&self, node: &impl NodeExt) -> NotificationContent {
+        let (title, log_level) = if node.unschedulable() {
+            (
+                format!(
+                    "Node `{}` is *unschedulable* in cluster `{}`",
+                    node.name(),
+                    self.cluster_name
+                ),
+                NotifierLogLevel::Error,
+            )
+        } else {
+            (
+                format!(
+                    "Node `{}` is *healthy* in cluster `{}`",
+                    node.name(),
+                    self.cluster_name
+                ),
+                NotifierLogLevel::Info,
+            )
+        };
+
+        NotificationContent::new(
+            title,
+            log_level,
+            vec![
+                vec![
+                    Field::entries("Conditions", node.status_conditions().unwrap_or_default()),
+                    Field::entries("Addresses", node.addresses().unwrap_or_default()),
+                ],
+                vec![Field::entries("Labels", node.labels())],
+            ],
+        )
+        .with_reason(node.unschedulable().then_some("Unschedulable"))
+    }
+
+    fn pod_content(&self, pod: &impl PodExt) -> Option<NotificationContent> {
+        let phase = pod.phase()?;
+
+        let name = pod.name();
+        let namespace_field = Field::code(
+            "Namespace",
+            pod.namespace().unwrap_or("<Unknown>".to_string()),
+        );
+        let ip_field = Field::code("IP Address", pod.ip_addr().unwrap_or(&"<None>".to_string()));
+
+        let failing_containers = pod.failing_containers();
+
+        // A crash looping pod still reports phase `Running`, so failing containers
+        // take precedence over the phase
+        let (title, log_level, reason) = if let Some(container) = failing_containers.first() {
+            (
+                format!(
+                    "Container `{}` of pod `{name}` is in *{}* in cluster `{}`",
+                    container.name,
+                    container
+                        .failure_reason()
+                        .map_or("<Unknown>", |r| r.as_str()),
+                    self.cluster_name
+                ),
+                NotifierLogLevel::Error,
+                container.failure_reason().cloned(),
+            )
+        } else {
+            let log_level = if phase == "Running" || phase == "Succeeded" {
+                NotifierLogLevel::Info
+            } else if phase == "Pending" {
+                NotifierLogLevel::Warn
+            } else {
+                NotifierLogLevel::Error
+            };
+
+            let reason =
+                (log_level > NotifierLogLevel::Info).then(|| pod.reason().unwrap_or(phase).clone());
+
+            (
+                format!(
+                    "Pod `{name}` is in phase *{phase}* in cluster `{}`",
+                    self.cluster_name
+                ),
+                log_level,
+                reason,
+            )
+        };
+
+        let mut rows = vec![vec![namespace_field, ip_field]];
+        rows.extend(failing_containers.iter().map(container_fields));
+        rows.push(vec![Field::entries("Labels", pod.labels())]);
+
+        Some(NotificationContent::new(title, log_level, rows).with_reason(reason))
+    }
+
+    fn event_content(&self, event: &impl EventExt) -> Option<NotificationContent> {
+        let typ = event.typ()?;
+
+        let log_level = if typ == "Normal" {
+            NotifierLogLevel::Info
+        } else {
+            NotifierLogLevel::Warn
+        };
+        let title = format!(
+            "*{}* events seen from the {} `{}`",
+            event.count().unwrap_or(0),
+            event
+                .involved_object_kind()
+                .unwrap_or(&"<Unknown Resource>".to_string()),
+            event
+                .involved_object_name()
+                .unwrap_or(&"<Unknown Name>".to_string())
+        );
+
+        let first_seen_field = Field::code(
+            "First Seen",
+            event
+                .first_timestamp()
+                .map(|t| t.to_string())
+                .unwrap_or("<Unknown>".to_string()),
+        );
+        let last_seen_field = Field::code(
+            "Last Seen",
+            event
+                .last_timestamp()
+                .map(|t| t.to_string())
+                .unwrap_or("<Unknown>".to_string()),
+        );
+        let message_field = Field::text("Message", event.message().unwrap_or(&"\"\"".to_string()));
+        let reason_field = Field::text("Reason", event.reason().unwrap_or(&"\"\"".to_string()));
+
+        Some(
+            NotificationContent::new(
+                title,
+                log_level,
+                vec![
+                    vec![first_seen_field, last_seen_field],
+                    vec![reason_field, message_field],
+                ],
+            )
+            .with_reason(event.reason().cloned()),
+        )
+    }
+
+    /// Builds a notification describing the rollout state of a workload
+    /// (e.g. a Deployment, StatefulSet or DaemonSet)
+    fn rollout_content(
+        &self,
+        kind: &str,
+        name: String,
+        namespace: Option<String>,
+        labels: &BTreeMap<String, String>,
+        conditions: BTreeMap<&String, &String>,
+        status: RolloutStatus,
+    ) -> NotificationContent {
+        let (title, log_level) = if let Some(reason) = status.stalled_reason.as_ref() {
+            (
+                format!(
+                    "{kind} `{name}` rollout has *stalled* (`{reason}`) in cluster `{}`",
+                    self.cluster_name
+                ),
+                NotifierLogLevel::Error,
+            )
+        } else if !status.is_complete() {
+            (
+                format!(
+                    "{kind} `{name}` is *rolling out* in cluster `{}`",
+                    self.cluster_name
+                ),
+                NotifierLogLevel::Warn,
+            )
+        } else {
+            (
+                format!(
+                    "{kind} `{name}` is *available* in cluster `{}`",
+                    self.cluster_name
+                ),
+                NotifierLogLevel::Info,
+            )
+        };
+
+        let namespace_field =
+            Field::code("Namespace", namespace.unwrap_or("<Unknown>".to_string()));
+        let replicas_field = Field::entries(
+            "Replicas",
+            [
+                ("Desired", status.desired),
+                ("Updated", status.updated),
+                ("Ready", status.ready),
+                ("Available", status.available),
+            ],
+        );
+
+        NotificationContent::new(
+            title,
+            log_level,
+            vec![
+                vec![namespace_field, replicas_field],
+                vec![
+                    Field::entries("Conditions", conditions),
+                    Field::entries("Labels", labels),
+                ],
+            ],
+        )
+        .with_reason(status.stalled_reason)
+    }
+
+    fn job_content(&self, job: &impl JobExt) -> NotificationContent {
+        let name = job.name();
+        let retries = job.failed_pods();
+
+        let (title, log_level) = if let Some(reason) = job.failure_reason() {
+            (
+                format!(
+                    "Job `{name}` has *failed* (`{reason}`) in cluster `{}`",
+                    self.cluster_name
+                ),
+                NotifierLogLevel::Error,
+            )
+        } else if job.complete() && retries > 0 {
+            (
+                format!(
+                    "Job `{name}` *succeeded after {retries} {}* in cluster `{}`",
+                    if retries == 1 { "retry" } else { "retries" },
+                    self.cluster_name
+                ),
+                NotifierLogLevel::Warn,
+            )
+        } else if job.complete() {
+            (
+                format!(
+                    "Job `{name}` has *completed* in cluster `{}`",
+                    self.cluster_name
+                ),
+                NotifierLogLevel::Info,
+            )
+        } else {
+            (
+                format!(
+                    "Job `{name}` is *running* in cluster `{}`",
+                    self.cluster_name
+                ),
+                NotifierLogLevel::Info,
+            )
+        };
+
+        let namespace_field = Field::code(
+            "Namespace",
+            job.namespace().unwrap_or("<Unknown>".to_string()),
+        );
+        let pods_field = Field::entries(
+            "Pods",
+            [
+                ("Active", job.active_pods()),
+                ("Succeeded", job.succeeded_pods()),
+                ("Failed", job.failed_pods()),
+                ("Backoff Limit", job.backoff_limit()),
+            ],
+        );
+
+        let mut rows = vec![
+            vec![namespace_field, pods_field],
+            vec![
+                Field::entries("Conditions", job.status_conditions().unwrap_or_default()),
+                Field::entries("Labels", job.labels()),
+            ],
+        ];
+
+        if let Some(message) = job.failure_message() {
+            rows.push(vec![Field::text("Message", message)]);
+        }
+
+        NotificationContent::new(title, log_level, rows).with_reason(job.failure_reason().cloned())
+    }
+
+    fn cronjob_content(
+        &self,
+        cron_job: &impl CronJobExt,
+        state: &ResourceState,
+    ) -> NotificationContent {
+        let name = cron_job.name();
+
+        let (title, log_level) = if state.phase.as_deref() == Some("MissedSchedule") {
+            (
+                format!(
+                    "CronJob `{name}` has *missed its schedule* in cluster `{}`. A job was expected at `{}`",
+                    self.cluster_name,
+                    cron_job
+                        .next_schedule_time()
+                        .map(|t| t.to_string())
+                        .unwrap_or("<Unknown>".to_string())
+                ),
+                NotifierLogLevel::Error,
+            )
+        } else if cron_job.suspended() {
+            (
+                format!(
+                    "CronJob `{name}` is *suspended* in cluster `{}`",
+                    self.cluster_name
+                ),
+                NotifierLogLevel::Info,
+            )
+        } else {
+            (
+                format!(
+                    "CronJob `{name}` is *on schedule* in cluster `{}`",
+                    self.cluster_name
+                ),
+                NotifierLogLevel::Info,
+            )
+        };
+
+        let namespace_field = Field::code(
+            "Namespace",
+            cron_job.namespace().unwrap_or("<Unknown>".to_string()),
+        );
+        let schedule_field = Field::code(
+            "Schedule",
+            cron_job.schedule().unwrap_or(&"<Unknown>".to_string()),
+        );
+        let last_scheduled_field = Field::code(
+            "Last Scheduled",
+            cron_job
+                .last_schedule_time()
+                .map(|t| t.to_string())
+                .unwrap_or("<Never>".to_string()),
+        );
+        let last_successful_field = Field::code(
+            "Last Successful",
+            cron_job
+                .last_successful_time()
+                .map(|t| t.to_string())
+                .unwrap_or("<Never>".to_string()),
+        );
+        let active_jobs_field = Field::code("Active Jobs", cron_job.active_jobs());
+
+        NotificationContent::new(
+            title,
+            log_level,
+            vec![
+                vec![namespace_field, schedule_field],
+                vec![last_scheduled_field, last_successful_field],
+                vec![
+                    active_jobs_field,
+                    Field::entries("Labels", cron_job.labels()),
+                ],
+            ],
+        )
+        .with_reason((state.phase.as_deref() == Some("MissedSchedule")).then_some("MissedSchedule"))
+    }
+
+    /// Builds a notification for an arbitrary resource, based on the standard
+    /// conditions in its status (if any)
+    fn dynamic_content(
+        &self,
+        obj: &impl DynamicObjectExt,
+        api_resource: &ApiResource,
+    ) -> NotificationContent {
+        let kind = &api_resource.kind;
+        let name = obj.name();
+        let conditions = obj.status_conditions();
+
+        let failing = conditions.iter().find(|c| c.is_failing());
+        let unknown = conditions.iter().find(|c| c.is_unknown());
+
+        let (title, log_level) = if let Some(condition) = failing {
+            (
+                format!(
+                    "{kind} `{name}` has failing condition *{}* (`{}`) in cluster `{}`",
+                    condition.type_,
+                    condition.reason.as_deref().unwrap_or("<Unknown Reason>"),
+                    self.cluster_name
+                ),
+                NotifierLogLevel::Error,
+            )
+        } else if let Some(condition) = unknown {
+            (
+                format!(
+                    "{kind} `{name}` has condition *{}* in an unknown state in cluster `{}`",
+                    condition.type_, self.cluster_name
+                ),
+                NotifierLogLevel::Warn,
+            )
+        } else {
+            (
+                format!(
+                    "{kind} `{name}` is *healthy* in cluster `{}`",
+                    self.cluster_name
+                ),
+                NotifierLogLevel::Info,
+            )
+        };
+
+        let namespace_field =
+            Field::code("Namespace", obj.namespace().unwrap_or("<None>".to_string()));
+        let api_version_field = Field::code("API Version", &api_resource.api_version);
+        let conditions_field = Field::entries(
+            "Conditions",
+            conditions.iter().map(|c| (&c.type_, &c.status)),
+        );
+
+        let mut rows = vec![
+            vec![namespace_field, api_version_field],
+            vec![conditions_field, Field::entries("Labels", obj.labels())],
+        ];
+
+        if let Some(message) = failing.or(unknown).and_then(|c| c.message.as_ref()) {
+            rows.push(vec![Field::text("Message", message)]);
+        }
+
+        let reason = failing.or(unknown).map(|c| c.type_.clone());
+
+        NotificationContent::new(title, log_level, rows).with_reason(reason)
+    }
+
+    /// Builds a notification for a resource which has been deleted from the cluster
+    fn deleted_content(&self, resource: &PackedResource) -> Option<NotificationContent> {
+        let log_level = match resource {
+            // Events are routinely garbage collected, so their removal isn't interesting
+            PackedResource::Event(_) => return None,
+            PackedResource::Node(_) => NotifierLogLevel::Warn,
+            PackedResource::Pod(pod) if pod.reason().is_some_and(|r| r == "Evicted") => {
+                NotifierLogLevel::Warn
+            }
+            _ => NotifierLogLevel::Info,
+        };
+
+        let title = format!(
+            "{} `{}` was *removed* from cluster `{}`",
+            resource.kind(),
+            resource.name(),
+            self.cluster_name
+        );
+
+        let mut rows = vec![];
+        if let Some(namespace) = resource.namespace() {
+            rows.push(vec![Field::code("Namespace", namespace)]);
+        }
+        rows.push(vec![Field::entries("Labels", resource.labels())]);
+
+        Some(NotificationContent::new(title, log_level, rows).with_reason(Some("Deleted")))
+    }
+}
+
+/// The fields describing a failing container
+fn container_fields(container: &PodContainerStatus) -> Vec<Field> {
+    let kind = if container.init {
+        "Init Container"
+    } else {
+        "Container"
+    };
+
+    vec![
+        Field::code(kind, &container.name),
+        Field::code(
+            "Reason",
+            container
+                .failure_reason()
+                .map_or("<Unknown>", |r| r.as_str()),
+        ),
+        Field::code(
+            "Exit Code",
+            container
+                .exit_code
+                .map_or("<None>".to_string(), |c| c.to_string()),
+        ),
+        Field::code("Restarts", container.restart_count),
+    ]
+}
@@ -0,0 +1,204 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::json;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+
+use super::content::{ContentBuilder, NotificationContent};
+use super::{
+    get_notification_color, impl_loggable, impl_resource_update_stream, Loggable, Notifier,
+    NotifierLogLevel,
+};
+
+use crate::state::ResourceUpdate;
+
+/// Discord rejects embeds exceeding these limits
+const MAX_DESCRIPTION_LENGTH: usize = 4096;
+const MAX_FIELDS: usize = 25;
+const MAX_FIELD_NAME_LENGTH: usize = 256;
+const MAX_FIELD_VALUE_LENGTH: usize = 1024;
+/// The combined length of an embed's description, field names and values and footer
+const MAX_EMBED_LENGTH: usize = 6000;
+
+/// Notifier which posts embeds to a Discord webhook
+pub struct DiscordNotifier {
+    rx: BroadcastStream<ResourceUpdate>,
+    webhook_url: reqwest::Url,
+    client: reqwest::Client,
+    log_level: NotifierLogLevel,
+    content_builder: ContentBuilder,
+}
+
+impl DiscordNotifier {
+    pub fn new(
+        rx: broadcast::Receiver<ResourceUpdate>,
+        webhook_url: reqwest::Url,
+        log_level: NotifierLogLevel,
+        cluster_name: String,
+    ) -> Self {
+        Self {
+            rx: BroadcastStream::new(rx),
+            webhook_url,
+            client: reqwest::Client::new(),
+            log_level,
+            content_builder: ContentBuilder::new(cluster_name),
+        }
+    }
+
+    /// Builds an embed colored by severity. The description holds the title and the
+    /// changes since the resource was last observed (if any). Fields of the same row
+    /// are rendered inline, followed by any attached logs
+    fn build_notification(&self, content: NotificationContent) -> DiscordNotification {
+        let mut description = content.markdown_title();
+
+        if !content.changes.is_empty() {
+            description.push_str("\n\n**Changes**");
+            for change in &content.changes {
+                description.push_str(&format!(
+                    "\n• `{}` : `{}` → `{}`",
+                    change.field,
+                    change.previous.as_deref().unwrap_or("<None>"),
+                    change.current.as_deref().unwrap_or("<None>")
+                ));
+            }
+        }
+
+        let description = truncate(&description, MAX_DESCRIPTION_LENGTH);
+        let footer = self.content_builder.cluster_name();
+
+        // Fields are dropped once the embed's total length would be exceeded. Logs come
+        // last, so they are trimmed or dropped before any other fields
+        let mut remaining =
+            MAX_EMBED_LENGTH.saturating_sub(description.chars().count() + footer.chars().count());
+        let mut fields = vec![];
+
+        for row in &content.rows {
+            let inline = row.len() > 1;
+            for field in row {
+                let name = truncate(&field.name, MAX_FIELD_NAME_LENGTH);
+                let value = truncate(&field.value.to_markdown(), MAX_FIELD_VALUE_LENGTH);
+
+                let length = name.chars().count() + value.chars().count();
+                if fields.len() == MAX_FIELDS || length > remaining {
+                    continue;
+                }
+
+                remaining -= length;
+                fields.push(json!({
+                    "name": name,
+                    "value": value,
+                    "inline": inline,
+                }));
+            }
+        }
+
+        for logs in &content.logs {
+            let name = truncate(
+                &format!("Logs of {}", logs.container),
+                MAX_FIELD_NAME_LENGTH,
+            );
+
+            // Keep the most recent lines which fit, leaving room for the code block
+            // delimiters
+            let room = MAX_FIELD_VALUE_LENGTH
+                .min(remaining.saturating_sub(name.chars().count()))
+                .saturating_sub(8);
+            if fields.len() == MAX_FIELDS || room == 0 {
+                break;
+            }
+
+            let content = if logs.content.trim().is_empty() {
+                "<No output>"
+            } else {
+                logs.content.trim_end()
+            };
+
+            let skip = content.chars().count().saturating_sub(room);
+            let value = format!("```\n{}```", content.chars().skip(skip).collect::<String>());
+
+            remaining -= name.chars().count() + value.chars().count();
+            fields.push(json!({
+                "name": name,
+                "value": value,
+                "inline": false,
+            }));
+        }
+
+        DiscordNotification {
+            inner: json!({
+                "embeds": [
+                    {
+                        "description": description,
+                        "color": get_embed_color(content.level),
+                        "fields": fields,
+                        "footer": { "text": footer },
+                        "timestamp": chrono::Utc::now().to_rfc3339(),
+                    }
+                ]
+            }),
+            level: content.level,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for DiscordNotifier {
+    type Notification = DiscordNotification;
+
+    fn create_notifications(&self, update: &ResourceUpdate) -> Vec<Self::Notification> {
+        self.content_builder
+            .build(update)
+            .into_iter()
+            .map(|content| self.build_notification(content))
+            .collect()
+    }
+
+    fn log_level(&self) -> NotifierLogLevel {
+        self.log_level
+    }
+
+    async fn emit_notification(&self, notification: Self::Notification) -> anyhow::Result<()> {
+        let res = self
+            .client
+            .post(self.webhook_url.clone())
+            .json(&notification.inner)
+            .send()
+            .await?;
+
+        tracing::info!(
+            "Received status {} upon emitting discord notification",
+            res.status()
+        );
+
+        res.error_for_status()?;
+
+        Ok(())
+    }
+}
+
+impl_resource_update_stream!(DiscordNotifier, rx);
+
+pub struct DiscordNotification {
+    level: NotifierLogLevel,
+    inner: serde_json::Value,
+}
+
+impl_loggable!(DiscordNotification, level);
+
+/// Converts the notification color palette into the integer form embeds expect
+fn get_embed_color(level: NotifierLogLevel) -> u32 {
+    u32::from_str_radix(get_notification_color(level).trim_start_matches('#'), 16)
+        .expect("notification colors should be hex encoded")
+}
+
+/// Truncates `s` to at most `max` characters, marking truncation with an ellipsis
+fn truncate(s: &str, max: usize) -> String {
+    match s.char_indices().nth(max) {
+        Some(_) => {
+            let mut truncated = s.chars().take(max - 1).collect::<String>();
+            truncated.push('…');
+            truncated
+        }
+        None => s.to_string(),
+    }
+}
@@ -0,0 +1,379 @@
+use async_trait::async_trait;
+use clap::ValueEnum;
+use futures::StreamExt;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+
+use super::content::{ContentBuilder, Field, FieldValue, NotificationContent};
+use super::{
+    get_notification_color, impl_loggable, impl_resource_update_stream, Loggable, Notifier,
+    NotifierLogLevel,
+};
+
+use crate::state::ResourceUpdate;
+
+/// How the connection to the SMTP server is secured
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum SmtpTls {
+    /// No encryption. Only suitable for local relays and testing
+    None,
+    /// Upgrade a plain connection with STARTTLS, usually on port 587
+    Starttls,
+    /// Implicit TLS, usually on port 465
+    Tls,
+}
+
+/// Recipients of email notifications. Notifications are sent to the recipients of
+/// all levels, as well as to those of their own level
+#[derive(Debug, Clone, Default)]
+pub struct EmailRecipients {
+    pub all: Vec<Mailbox>,
+    pub info: Vec<Mailbox>,
+    pub warn: Vec<Mailbox>,
+    pub error: Vec<Mailbox>,
+}
+
+impl EmailRecipients {
+    fn for_level(&self, level: NotifierLogLevel) -> impl Iterator<Item = &Mailbox> {
+        let leveled = match level {
+            NotifierLogLevel::Info => &self.info,
+            NotifierLogLevel::Warn => &self.warn,
+            NotifierLogLevel::Error => &self.error,
+        };
+
+        self.all.iter().chain(leveled)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    /// The port of the SMTP server, if not the default for `tls`
+    pub smtp_port: Option<u16>,
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub from: Mailbox,
+    pub recipients: EmailRecipients,
+}
+
+/// Notifier which sends multipart HTML and plain-text emails over SMTP
+pub struct EmailNotifier {
+    rx: BroadcastStream<ResourceUpdate>,
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    recipients: EmailRecipients,
+    log_level: NotifierLogLevel,
+    content_builder: ContentBuilder,
+}
+
+impl EmailNotifier {
+    /// Creates a notifier sending through the configured SMTP server. Fails if the TLS
+    /// configuration for the server cannot be built
+    pub fn new(
+        rx: broadcast::Receiver<ResourceUpdate>,
+        config: EmailConfig,
+        log_level: NotifierLogLevel,
+        cluster_name: String,
+    ) -> anyhow::Result<Self> {
+        let mut builder = match config.tls {
+            SmtpTls::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.smtp_host)
+            }
+            SmtpTls::Starttls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)?
+            }
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.smtp_host)?,
+        };
+
+        if let Some(port) = config.smtp_port {
+            builder = builder.port(port);
+        }
+
+        if let Some(username) = config.username {
+            builder = builder.credentials(Credentials::new(
+                username,
+                config.password.unwrap_or_default(),
+            ));
+        }
+
+        Ok(Self {
+            rx: BroadcastStream::new(rx),
+            transport: builder.build(),
+            from: config.from,
+            recipients: config.recipients,
+            log_level,
+            content_builder: ContentBuilder::new(cluster_name),
+        })
+    }
+
+    /// Builds an email for the recipients of the content's level, or [`None`] if there
+    /// are no such recipients
+    fn build_notification(&self, content: NotificationContent) -> Option<EmailNotification> {
+        let mut builder = Message::builder().from(self.from.clone()).subject(format!(
+            "[{}] {}",
+            content.level.to_string().to_uppercase(),
+            content.plain_title()
+        ));
+
+        let mut has_recipients = false;
+        for recipient in self.recipients.for_level(content.level) {
+            builder = builder.to(recipient.clone());
+            has_recipients = true;
+        }
+
+        if !has_recipients {
+            return None;
+        }
+
+        let message = builder.multipart(MultiPart::alternative_plain_html(
+            format_text_body(&content),
+            format_html_body(&content),
+        ));
+
+        match message {
+            Ok(message) => Some(EmailNotification {
+                level: content.level,
+                message,
+            }),
+            Err(e) => {
+                tracing::error!("Failed to build email notification: {e}");
+                None
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for EmailNotifier {
+    type Notification = EmailNotification;
+
+    fn create_notifications(&self, update: &ResourceUpdate) -> Vec<Self::Notification> {
+        self.content_builder
+            .build(update)
+            .and_then(|content| self.build_notification(content))
+            .into_iter()
+            .collect()
+    }
+
+    fn log_level(&self) -> NotifierLogLevel {
+        self.log_level
+    }
+
+    async fn emit_notification(&self, notification: Self::Notification) -> anyhow::Result<()> {
+        let res = self.transport.send(notification.message).await?;
+
+        tracing::info!(
+            "Received code {} upon emitting email notification",
+            res.code()
+        );
+
+        Ok(())
+    }
+}
+
+impl_resource_update_stream!(EmailNotifier, rx);
+
+pub struct EmailNotification {
+    level: NotifierLogLevel,
+    message: Message,
+}
+
+impl_loggable!(EmailNotification, level);
+
+/// Formats the plain-text body, listing each field on its own line
+fn format_text_body(content: &NotificationContent) -> String {
+    let mut body = format!("{}\n", content.plain_title());
+
+    if !content.changes.is_empty() {
+        body.push_str("\nChanges:\n");
+        for change in &content.changes {
+            body.push_str(&format!(
+                "  - {}: {} -> {}\n",
+                change.field,
+                change.previous.as_deref().unwrap_or("<None>"),
+                change.current.as_deref().unwrap_or("<None>")
+            ));
+        }
+    }
+
+    body.push('\n');
+    for field in content.rows.iter().flatten() {
+        match &field.value {
+            FieldValue::Code(value) | FieldValue::Text(value) => {
+                body.push_str(&format!("{}: {value}\n", field.name));
+            }
+            FieldValue::Entries(entries) if entries.is_empty() => {
+                body.push_str(&format!("{}: <None>\n", field.name));
+            }
+            FieldValue::Entries(entries) => {
+                body.push_str(&format!("{}:\n", field.name));
+                for (key, val) in entries {
+                    body.push_str(&format!("  - {key}: {val}\n"));
+                }
+            }
+        }
+    }
+
+    for logs in &content.logs {
+        body.push_str(&format!(
+            "\nLogs of {}:\n{}\n",
+            logs.container, logs.content
+        ));
+    }
+
+    body
+}
+
+/// Formats the HTML body. The title is highlighted with the level's color, followed by
+/// the changes, a table with one row per row of fields and any attached logs
+fn format_html_body(content: &NotificationContent) -> String {
+    let mut body = format!(
+        "<div style=\"border-left: 4px solid {}; padding-left: 12px\">\n<p>{}</p>\n",
+        get_notification_color(content.level),
+        format_html_title(&content.title)
+    );
+
+    if !content.changes.is_empty() {
+        body.push_str("<p><b>Changes</b></p>\n<ul>\n");
+        for change in &content.changes {
+            body.push_str(&format!(
+                "<li><code>{}</code> : <code>{}</code> &rarr; <code>{}</code></li>\n",
+                escape_html(&change.field),
+                escape_html(change.previous.as_deref().unwrap_or("<None>")),
+                escape_html(change.current.as_deref().unwrap_or("<None>"))
+            ));
+        }
+        body.push_str("</ul>\n");
+    }
+
+    body.push_str("<table cellpadding=\"6\">\n");
+    for fields in &content.rows {
+        body.push_str("<tr>\n");
+        for field in fields {
+            body.push_str(&format!(
+                "<td valign=\"top\"><b>{}</b><br>{}</td>\n",
+                escape_html(&field.name),
+                format_html_value(field)
+            ));
+        }
+        body.push_str("</tr>\n");
+    }
+    body.push_str("</table>\n");
+
+    for logs in &content.logs {
+        body.push_str(&format!(
+            "<p><b>Logs of <code>{}</code></b></p>\n<pre>{}</pre>\n",
+            escape_html(&logs.container),
+            escape_html(&logs.content)
+        ));
+    }
+
+    body.push_str("</div>\n");
+
+    body
+}
+
+fn format_html_value(field: &Field) -> String {
+    match &field.value {
+        FieldValue::Code(value) => format!("<code>{}</code>", escape_html(value)),
+        FieldValue::Text(value) => escape_html(value),
+        FieldValue::Entries(entries) if entries.is_empty() => "&lt;None&gt;".to_string(),
+        FieldValue::Entries(entries) => {
+            let items = entries
+                .iter()
+                .map(|(key, val)| {
+                    format!(
+                        "<li><code>{}</code> : <code>{}</code></li>",
+                        escape_html(key),
+                        escape_html(val)
+                    )
+                })
+                .collect::<String>();
+
+            format!("<ul>{items}</ul>")
+        }
+    }
+}
+
+/// Converts a title's backticks and asterisks into code and bold elements
+fn format_html_title(title: &str) -> String {
+    let mut html = String::new();
+    let mut code = false;
+    let mut bold = false;
+
+    for c in escape_html(title).chars() {
+        match c {
+            '`' => {
+                html.push_str(if code { "</code>" } else { "<code>" });
+                code = !code;
+            }
+            '*' if !code => {
+                html.push_str(if bold { "</b>" } else { "<b>" });
+                bold = !bold;
+            }
+            c => html.push(c),
+        }
+    }
+
+    // Close tags left open by an unmatched marker, so they don't leak into the body
+    if bold {
+        html.push_str("</b>");
+    }
+    if code {
+        html.push_str("</code>");
+    }
+
+    html
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_html() {
+        assert_eq!(
+            escape_html(r#"<script>alert("x & y")</script>"#),
+            "&lt;script&gt;alert(&quot;x &amp; y&quot;)&lt;/script&gt;"
+        );
+        assert_eq!(escape_html("&lt;"), "&amp;lt;");
+        assert_eq!(escape_html("plain"), "plain");
+    }
+
+    #[test]
+    fn formats_titles_as_html() {
+        assert_eq!(
+            format_html_title("Pod `web-0` is in phase *Failed*"),
+            "Pod <code>web-0</code> is in phase <b>Failed</b>"
+        );
+        assert_eq!(
+            format_html_title("Pod `<web>` & *a*"),
+            "Pod <code>&lt;web&gt;</code> &amp; <b>a</b>"
+        );
+    }
+
+    #[test]
+    fn keeps_asterisks_within_code() {
+        assert_eq!(
+            format_html_title("Schedule `*/5 * * * *` was *missed*"),
+            "Schedule <code>*/5 * * * *</code> was <b>missed</b>"
+        );
+    }
+
+    #[test]
+    fn closes_unmatched_markers() {
+        assert_eq!(format_html_title("Pod `web-0"), "Pod <code>web-0</code>");
+        assert_eq!(format_html_title("Phase *Failed"), "Phase <b>Failed</b>");
+    }
+}
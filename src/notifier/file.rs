@@ -0,0 +1,363 @@
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures::StreamExt;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+
+use super::content::{ContentBuilder, NotificationRecord};
+use super::{impl_loggable, impl_resource_update_stream, Loggable, Notifier, NotifierLogLevel};
+
+use crate::state::ResourceUpdate;
+
+#[derive(Debug, Clone)]
+pub struct FileConfig {
+    /// The file records are appended to
+    pub path: PathBuf,
+    /// Rotate the file once writing a record would grow it beyond this many bytes
+    pub max_bytes: Option<u64>,
+    /// Rotate the file once it is this old
+    pub max_age: Option<Duration>,
+    /// The number of rotated files to keep. Rotated files are named after the file,
+    /// suffixed with `.1` for the most recent up to `.<max_files>` for the oldest
+    pub max_files: usize,
+    /// Whether to gzip rotated files, which are then suffixed with `.gz`
+    pub compress: bool,
+}
+
+/// Notifier which appends each notification to a file as a line of JSON, for a
+/// durable local record of notifications
+pub struct FileNotifier {
+    rx: BroadcastStream<ResourceUpdate>,
+    file: Arc<Mutex<RotatingFile>>,
+    log_level: NotifierLogLevel,
+    content_builder: ContentBuilder,
+}
+
+impl FileNotifier {
+    /// Creates a notifier appending to the configured file. Fails if the file can't be
+    /// opened
+    pub fn new(
+        rx: broadcast::Receiver<ResourceUpdate>,
+        config: FileConfig,
+        log_level: NotifierLogLevel,
+        cluster_name: String,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            rx: BroadcastStream::new(rx),
+            file: Arc::new(Mutex::new(RotatingFile::open(config)?)),
+            log_level,
+            content_builder: ContentBuilder::new(cluster_name),
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for FileNotifier {
+    type Notification = FileNotification;
+
+    fn create_notifications(&self, update: &ResourceUpdate) -> Vec<Self::Notification> {
+        self.content_builder
+            .build(update)
+            .into_iter()
+            .filter_map(|content| {
+                let record =
+                    NotificationRecord::new(self.content_builder.cluster_name(), update, &content);
+
+                match serde_json::to_vec(&record) {
+                    Ok(mut line) => {
+                        line.push(b'\n');
+
+                        Some(FileNotification {
+                            level: content.level,
+                            line,
+                        })
+                    }
+                    Err(e) => {
+                        tracing::error!("Failed to serialize file notification: {e}");
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn log_level(&self) -> NotifierLogLevel {
+        self.log_level
+    }
+
+    async fn emit_notification(&self, notification: Self::Notification) -> anyhow::Result<()> {
+        let file = self.file.clone();
+
+        // Rotating may compress a whole file, so keep blocking I/O off the runtime
+        tokio::task::spawn_blocking(move || {
+            file.lock()
+                .expect("file lock poisoned")
+                .write(&notification.line)
+        })
+        .await??;
+
+        Ok(())
+    }
+}
+
+impl_resource_update_stream!(FileNotifier, rx);
+
+pub struct FileNotification {
+    level: NotifierLogLevel,
+    line: Vec<u8>,
+}
+
+impl_loggable!(FileNotification, level);
+
+/// A file which is rotated once it grows too large or old
+struct RotatingFile {
+    config: FileConfig,
+    file: File,
+    size: u64,
+    /// When the file was created, so that its age carries over restarts
+    created_at: SystemTime,
+}
+
+impl RotatingFile {
+    fn open(config: FileConfig) -> io::Result<Self> {
+        if let Some(parent) = config.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&config.path)?;
+        let metadata = file.metadata()?;
+
+        Ok(Self {
+            config,
+            file,
+            size: metadata.len(),
+            // Not every filesystem records creation times, in which case the last
+            // modification is the best estimate available
+            created_at: metadata
+                .created()
+                .or_else(|_| metadata.modified())
+                .unwrap_or_else(|_| SystemTime::now()),
+        })
+    }
+
+    /// Appends `line`, rotating the file first if necessary. The line is synced to
+    /// disk before returning so that a crash doesn't lose it
+    fn write(&mut self, line: &[u8]) -> io::Result<()> {
+        if self.should_rotate(line.len() as u64) {
+            self.rotate()?;
+        }
+
+        self.file.write_all(line)?;
+        self.file.sync_data()?;
+        self.size += line.len() as u64;
+
+        Ok(())
+    }
+
+    fn should_rotate(&self, len: u64) -> bool {
+        if self.size == 0 {
+            return false;
+        }
+
+        let too_large = self
+            .config
+            .max_bytes
+            .is_some_and(|max_bytes| self.size + len > max_bytes);
+        let too_old = self.config.max_age.is_some_and(|max_age| {
+            self.created_at
+                .elapsed()
+                .is_ok_and(|elapsed| elapsed >= max_age)
+        });
+
+        too_large || too_old
+    }
+
+    /// Shifts each rotated file up by one, dropping the oldest, then moves the current
+    /// file into place as the most recent and reopens it
+    fn rotate(&mut self) -> io::Result<()> {
+        let path = &self.config.path;
+        let max_files = self.config.max_files;
+        let suffix = if self.config.compress { ".gz" } else { "" };
+
+        if max_files == 0 {
+            fs::remove_file(path)?;
+        } else {
+            remove_if_exists(&rotated_path(path, max_files, suffix))?;
+
+            for i in (1..max_files).rev() {
+                let from = rotated_path(path, i, suffix);
+                if from.exists() {
+                    fs::rename(from, rotated_path(path, i + 1, suffix))?;
+                }
+            }
+
+            let rotated = rotated_path(path, 1, "");
+            fs::rename(path, &rotated)?;
+
+            if self.config.compress {
+                compress(&rotated, &rotated_path(path, 1, suffix))?;
+                fs::remove_file(&rotated)?;
+            }
+        }
+
+        *self = Self::open(self.config.clone())?;
+
+        Ok(())
+    }
+}
+
+/// The path of the `index`th most recent rotated file, e.g. `notifications.jsonl.1.gz`
+fn rotated_path(path: &Path, index: usize, suffix: &str) -> PathBuf {
+    let mut rotated = OsString::from(path);
+    rotated.push(format!(".{index}{suffix}"));
+
+    PathBuf::from(rotated)
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+fn compress(from: &Path, to: &Path) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(File::create(to)?, Compression::default());
+    io::copy(&mut File::open(from)?, &mut encoder)?;
+    encoder.finish()?.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    use super::*;
+
+    /// Creates an empty directory for a test's files
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("k8s-notifier-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        dir
+    }
+
+    fn config(dir: &Path, max_files: usize, compress: bool) -> FileConfig {
+        FileConfig {
+            path: dir.join("notifications.jsonl"),
+            max_bytes: Some(10),
+            max_age: None,
+            max_files,
+            compress,
+        }
+    }
+
+    fn read(path: &Path) -> String {
+        fs::read_to_string(path).unwrap()
+    }
+
+    #[test]
+    fn should_rotate_once_too_large() {
+        let dir = test_dir("should-rotate-size");
+        let mut file = RotatingFile::open(config(&dir, 1, false)).unwrap();
+
+        // An empty file is never rotated, even if a single line exceeds the limit
+        assert!(!file.should_rotate(20));
+
+        file.write(b"12345\n").unwrap();
+        assert!(!file.should_rotate(4));
+        assert!(file.should_rotate(5));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn should_rotate_once_too_old() {
+        let dir = test_dir("should-rotate-age");
+        let mut config = config(&dir, 1, false);
+        config.max_bytes = None;
+        config.max_age = Some(Duration::from_secs(60));
+
+        let mut file = RotatingFile::open(config).unwrap();
+        file.write(b"line\n").unwrap();
+        assert!(!file.should_rotate(5));
+
+        file.created_at = SystemTime::now() - Duration::from_secs(61);
+        assert!(file.should_rotate(5));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rotate_shifts_files_and_drops_the_oldest() {
+        let dir = test_dir("rotate-shift");
+        let config = config(&dir, 2, false);
+        let mut file = RotatingFile::open(config.clone()).unwrap();
+
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            file.write(line.as_bytes()).unwrap();
+        }
+
+        assert_eq!(read(&config.path), "fourth\n");
+        assert_eq!(read(&rotated_path(&config.path, 1, "")), "third\n");
+        assert_eq!(read(&rotated_path(&config.path, 2, "")), "second\n");
+        assert!(!rotated_path(&config.path, 3, "").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rotate_without_keeping_files_truncates() {
+        let dir = test_dir("rotate-none");
+        let config = config(&dir, 0, false);
+        let mut file = RotatingFile::open(config.clone()).unwrap();
+
+        file.write(b"first\n").unwrap();
+        file.write(b"second\n").unwrap();
+
+        assert_eq!(read(&config.path), "second\n");
+        assert!(!rotated_path(&config.path, 1, "").exists());
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn rotate_compresses_rotated_files() {
+        let dir = test_dir("rotate-compress");
+        let config = config(&dir, 2, true);
+        let mut file = RotatingFile::open(config.clone()).unwrap();
+
+        for line in ["first\n", "second\n", "third\n"] {
+            file.write(line.as_bytes()).unwrap();
+        }
+
+        let decompress = |path: PathBuf| {
+            let mut content = String::new();
+            GzDecoder::new(File::open(path).unwrap())
+                .read_to_string(&mut content)
+                .unwrap();
+
+            content
+        };
+
+        assert_eq!(read(&config.path), "third\n");
+        assert!(!rotated_path(&config.path, 1, "").exists());
+        assert_eq!(decompress(rotated_path(&config.path, 1, ".gz")), "second\n");
+        assert_eq!(decompress(rotated_path(&config.path, 2, ".gz")), "first\n");
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures::StreamExt;
+use rdkafka::config::ClientConfig;
+use rdkafka::producer::{FutureProducer, FutureRecord};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+
+use super::content::{ContentBuilder, NotificationRecord};
+use super::{impl_loggable, impl_resource_update_stream, Loggable, Notifier, NotifierLogLevel};
+
+use crate::state::ResourceUpdate;
+
+/// How long to wait for room in the producer's queue before giving up on a message
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Notifier which publishes each notification as a JSON message to a Kafka topic.
+/// Messages are keyed by the object's namespace and name, so that the messages of an
+/// object land on the same partition and stay in order
+pub struct KafkaNotifier {
+    rx: BroadcastStream<ResourceUpdate>,
+    producer: FutureProducer,
+    topic: String,
+    log_level: NotifierLogLevel,
+    content_builder: ContentBuilder,
+}
+
+impl KafkaNotifier {
+    /// Creates a notifier producing to `topic` on the cluster at `brokers`. `config`
+    /// holds additional librdkafka properties, e.g. `security.protocol`
+    pub fn new(
+        rx: broadcast::Receiver<ResourceUpdate>,
+        brokers: String,
+        topic: String,
+        config: Vec<(String, String)>,
+        log_level: NotifierLogLevel,
+        cluster_name: String,
+    ) -> anyhow::Result<Self> {
+        let mut client_config = ClientConfig::new();
+        client_config
+            .set("bootstrap.servers", brokers)
+            // Retries must not reorder the messages of an object
+            .set("enable.idempotence", "true");
+
+        for (key, value) in config {
+            client_config.set(key, value);
+        }
+
+        Ok(Self {
+            rx: BroadcastStream::new(rx),
+            producer: client_config.create()?,
+            topic,
+            log_level,
+            content_builder: ContentBuilder::new(cluster_name),
+        })
+    }
+}
+
+#[async_trait]
+impl Notifier for KafkaNotifier {
+    type Notification = KafkaNotification;
+
+    fn create_notifications(&self, update: &ResourceUpdate) -> Vec<Self::Notification> {
+        self.content_builder
+            .build(update)
+            .into_iter()
+            .filter_map(|content| {
+                let record =
+                    NotificationRecord::new(self.content_builder.cluster_name(), update, &content);
+
+                match serde_json::to_vec(&record) {
+                    Ok(payload) => Some(KafkaNotification {
+                        level: content.level,
+                        key: record.key(),
+                        payload,
+                    }),
+                    Err(e) => {
+                        tracing::error!("Failed to serialize kafka notification: {e}");
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn log_level(&self) -> NotifierLogLevel {
+        self.log_level
+    }
+
+    async fn emit_notification(&self, notification: Self::Notification) -> anyhow::Result<()> {
+        let record = FutureRecord::to(&self.topic)
+            .key(&notification.key)
+            .payload(&notification.payload);
+
+        let (partition, offset) = self
+            .producer
+            .send(record, QUEUE_TIMEOUT)
+            .await
+            .map_err(|(e, _)| e)?;
+
+        tracing::info!("Published kafka notification to partition {partition} at offset {offset}");
+
+        Ok(())
+    }
+}
+
+impl_resource_update_stream!(KafkaNotifier, rx);
+
+pub struct KafkaNotification {
+    level: NotifierLogLevel,
+    key: String,
+    payload: Vec<u8>,
+}
+
+impl_loggable!(KafkaNotification, level);
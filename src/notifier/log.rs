@@ -1,22 +1,47 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
 use futures::StreamExt;
+use serde::Serialize;
 use tokio::sync::broadcast;
 use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
 
-use super::{impl_loggable, impl_packed_resource_stream, Loggable, Notifier, NotifierLogLevel};
+use super::content::ContentBuilder;
+use super::{impl_loggable, impl_resource_update_stream, Loggable, Notifier, NotifierLogLevel};
+
+use crate::state::ResourceUpdate;
 
-use crate::resource::PackedResource;
+/// How the log notifier outputs notifications
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum LogFormat {
+    /// Human readable log lines, emitted through the application's logger
+    Text,
+    /// One JSON object per line on stdout, for log pipelines to index
+    Json,
+}
 
 pub struct LogNotifier {
-    rx: BroadcastStream<PackedResource>,
+    rx: BroadcastStream<ResourceUpdate>,
     log_level: NotifierLogLevel,
+    format: LogFormat,
+    content_builder: ContentBuilder,
 }
 
 impl LogNotifier {
-    pub fn new(rx: broadcast::Receiver<PackedResource>, log_level: NotifierLogLevel) -> Self {
+    pub fn new(
+        rx: broadcast::Receiver<ResourceUpdate>,
+        log_level: NotifierLogLevel,
+        format: LogFormat,
+        cluster_name: String,
+    ) -> Self {
         Self {
             rx: BroadcastStream::new(rx),
             log_level,
+            format,
+            content_builder: ContentBuilder::new(cluster_name),
         }
     }
 }
@@ -25,26 +50,24 @@ impl LogNotifier {
 impl Notifier for LogNotifier {
     type Notification = LogNotification;
 
-    fn create_notifications(&self, resource: &PackedResource) -> Vec<Self::Notification> {
-        let mut notifications = vec![];
-
-        // TODO(rkrishn7): Implement
-        match resource {
-            PackedResource::Node(_) => notifications.push(LogNotification {
-                level: NotifierLogLevel::Info,
-                message: "Node seen".to_string(),
-            }),
-            PackedResource::Pod(_) => notifications.push(LogNotification {
-                level: NotifierLogLevel::Info,
-                message: "Pod seen".to_string(),
-            }),
-            PackedResource::Event(_) => notifications.push(LogNotification {
-                level: NotifierLogLevel::Info,
-                message: "Event seen".to_string(),
-            }),
-        }
-
-        notifications
+    fn create_notifications(&self, update: &ResourceUpdate) -> Vec<Self::Notification> {
+        self.content_builder
+            .build(update)
+            .into_iter()
+            .map(|content| LogNotification {
+                timestamp: Utc::now(),
+                cluster: self.content_builder.cluster_name().to_string(),
+                level: content.level,
+                kind: update.resource.kind(),
+                namespace: update.resource.namespace(),
+                name: update.resource.name(),
+                change: update.change.to_string(),
+                phase: update.current.phase.clone(),
+                conditions: update.current.conditions.clone(),
+                reason: content.reason.clone(),
+                message: content.plain_title(),
+            })
+            .collect()
     }
 
     fn log_level(&self) -> NotifierLogLevel {
@@ -52,15 +75,11 @@ impl Notifier for LogNotifier {
     }
 
     async fn emit_notification(&self, notification: Self::Notification) -> anyhow::Result<()> {
-        match notification.level {
-            NotifierLogLevel::Info => {
-                tracing::info!("{}", notification.message);
-            }
-            NotifierLogLevel::Warn => {
-                tracing::warn!("{}", notification.message);
-            }
-            NotifierLogLevel::Error => {
-                tracing::error!("{}", notification.message);
+        match self.format {
+            LogFormat::Text => notification.log(),
+            LogFormat::Json => {
+                let line = serde_json::to_string(&notification)?;
+                writeln!(std::io::stdout().lock(), "{line}")?;
             }
         }
 
@@ -68,11 +87,53 @@ impl Notifier for LogNotifier {
     }
 }
 
-impl_packed_resource_stream!(LogNotifier, rx);
+impl_resource_update_stream!(LogNotifier, rx);
 
+/// A structured record of a notification, serialized as a single line in the JSON
+/// format
+#[derive(Debug, Clone, Serialize)]
 pub struct LogNotification {
+    timestamp: DateTime<Utc>,
+    cluster: String,
     level: NotifierLogLevel,
+    kind: String,
+    namespace: Option<String>,
+    name: String,
+    /// How the resource changed, one of `applied`, `deleted` or `restarted`
+    change: String,
+    phase: Option<String>,
+    conditions: BTreeMap<String, String>,
+    reason: Option<String>,
     message: String,
 }
 
+impl LogNotification {
+    /// Logs the notification at its level, with its attributes as structured fields
+    fn log(&self) {
+        let namespace = self.namespace.as_deref().unwrap_or_default();
+        let phase = self.phase.as_deref().unwrap_or_default();
+        let reason = self.reason.as_deref().unwrap_or_default();
+
+        macro_rules! log {
+            ($macro:ident) => {
+                tracing::$macro!(
+                    kind = %self.kind,
+                    namespace,
+                    name = %self.name,
+                    phase,
+                    reason,
+                    "{}",
+                    self.message
+                )
+            };
+        }
+
+        match self.level {
+            NotifierLogLevel::Info => log!(info),
+            NotifierLogLevel::Warn => log!(warn),
+            NotifierLogLevel::Error => log!(error),
+        }
+    }
+}
+
 impl_loggable!(LogNotification, level);
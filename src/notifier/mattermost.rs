@@ -0,0 +1,157 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::json;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+
+use super::content::{ContentBuilder, NotificationContent};
+use super::{
+    get_notification_color, impl_loggable, impl_resource_update_stream, Loggable, Notifier,
+    NotifierLogLevel,
+};
+
+use crate::state::ResourceUpdate;
+
+/// Notifier which posts Slack-compatible message attachments to a Mattermost
+/// incoming webhook
+pub struct MattermostNotifier {
+    rx: BroadcastStream<ResourceUpdate>,
+    webhook_url: reqwest::Url,
+    channel: Option<String>,
+    client: reqwest::Client,
+    log_level: NotifierLogLevel,
+    content_builder: ContentBuilder,
+}
+
+impl MattermostNotifier {
+    /// Creates a notifier posting to `webhook_url`. Messages are posted to the webhook's
+    /// default channel unless `channel` is set
+    pub fn new(
+        rx: broadcast::Receiver<ResourceUpdate>,
+        webhook_url: reqwest::Url,
+        channel: Option<String>,
+        log_level: NotifierLogLevel,
+        cluster_name: String,
+    ) -> Self {
+        Self {
+            rx: BroadcastStream::new(rx),
+            webhook_url,
+            channel,
+            client: reqwest::Client::new(),
+            log_level,
+            content_builder: ContentBuilder::new(cluster_name),
+        }
+    }
+
+    /// Builds an attachment colored by severity. Its text holds the title and the
+    /// changes since the resource was last observed (if any). Fields of the same row
+    /// are rendered side by side, followed by any attached logs
+    fn build_notification(&self, content: NotificationContent) -> MattermostNotification {
+        let mut text = content.markdown_title();
+
+        if !content.changes.is_empty() {
+            text.push_str("\n\n**Changes**");
+            for change in &content.changes {
+                text.push_str(&format!(
+                    "\n- `{}` : `{}` → `{}`",
+                    change.field,
+                    change.previous.as_deref().unwrap_or("<None>"),
+                    change.current.as_deref().unwrap_or("<None>")
+                ));
+            }
+        }
+
+        let mut fields = vec![];
+        for row in &content.rows {
+            let short = row.len() > 1;
+            fields.extend(row.iter().map(|field| {
+                json!({
+                    "title": field.name,
+                    "value": field.value.to_markdown(),
+                    "short": short,
+                })
+            }));
+        }
+
+        fields.extend(content.logs.iter().map(|logs| {
+            let run = if logs.previous { "previous" } else { "current" };
+            let truncated = if logs.truncated { " (truncated)" } else { "" };
+            let content = if logs.content.trim().is_empty() {
+                "<No output>"
+            } else {
+                logs.content.trim_end()
+            };
+
+            json!({
+                "title": format!("Logs of {} from its {run} run{truncated}", logs.container),
+                "value": format!("```\n{content}\n```"),
+                "short": false,
+            })
+        }));
+
+        let mut inner = json!({
+            "attachments": [
+                {
+                    "fallback": content.plain_title(),
+                    "color": get_notification_color(content.level),
+                    "text": text,
+                    "fields": fields,
+                    "footer": self.content_builder.cluster_name(),
+                }
+            ]
+        });
+
+        if let Some(channel) = &self.channel {
+            inner["channel"] = json!(channel);
+        }
+
+        MattermostNotification {
+            inner,
+            level: content.level,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for MattermostNotifier {
+    type Notification = MattermostNotification;
+
+    fn create_notifications(&self, update: &ResourceUpdate) -> Vec<Self::Notification> {
+        self.content_builder
+            .build(update)
+            .into_iter()
+            .map(|content| self.build_notification(content))
+            .collect()
+    }
+
+    fn log_level(&self) -> NotifierLogLevel {
+        self.log_level
+    }
+
+    async fn emit_notification(&self, notification: Self::Notification) -> anyhow::Result<()> {
+        let res = self
+            .client
+            .post(self.webhook_url.clone())
+            .json(&notification.inner)
+            .send()
+            .await?;
+
+        tracing::info!(
+            "Received status {} upon emitting mattermost notification",
+            res.status()
+        );
+
+        res.error_for_status()?;
+
+        Ok(())
+    }
+}
+
+impl_resource_update_stream!(MattermostNotifier, rx);
+
+pub struct MattermostNotification {
+    level: NotifierLogLevel,
+    inner: serde_json::Value,
+}
+
+impl_loggable!(MattermostNotification, level);
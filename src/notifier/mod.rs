@@ -2,29 +2,74 @@ use async_trait::async_trait;
 use clap::ValueEnum;
 use futures::future;
 use futures::stream::StreamExt;
+use serde::Serialize;
 use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
 
-use crate::resource::PackedResource;
-
+use crate::state::ResourceUpdate;
+
+pub mod alert;
+pub mod alertmanager;
+pub mod cloudevents;
+pub mod content;
+pub mod discord;
+pub mod email;
+pub mod file;
+#[cfg(feature = "kafka")]
+pub mod kafka;
 pub mod log;
+pub mod mattermost;
+#[cfg(feature = "nats")]
+pub mod nats;
+pub mod opsgenie;
+pub mod pagerduty;
 pub mod slack;
+pub mod teams;
+pub mod webhook;
 
 #[derive(Debug, Clone, ValueEnum)]
 pub enum NotifierType {
+    Alertmanager,
+    Discord,
+    Email,
+    File,
+    #[cfg(feature = "kafka")]
+    Kafka,
     Log,
+    Mattermost,
+    #[cfg(feature = "nats")]
+    Nats,
+    Opsgenie,
+    #[value(name = "pagerduty")]
+    PagerDuty,
     Slack,
+    Teams,
+    Webhook,
 }
 
 impl std::fmt::Display for NotifierType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            NotifierType::Alertmanager => write!(f, "alertmanager"),
+            NotifierType::Discord => write!(f, "discord"),
+            NotifierType::Email => write!(f, "email"),
+            NotifierType::File => write!(f, "file"),
+            #[cfg(feature = "kafka")]
+            NotifierType::Kafka => write!(f, "kafka"),
             NotifierType::Log => write!(f, "log"),
+            NotifierType::Mattermost => write!(f, "mattermost"),
+            #[cfg(feature = "nats")]
+            NotifierType::Nats => write!(f, "nats"),
+            NotifierType::Opsgenie => write!(f, "opsgenie"),
+            NotifierType::PagerDuty => write!(f, "pagerduty"),
             NotifierType::Slack => write!(f, "slack"),
+            NotifierType::Teams => write!(f, "teams"),
+            NotifierType::Webhook => write!(f, "webhook"),
         }
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord, ValueEnum)]
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Eq, Ord, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
 pub enum NotifierLogLevel {
     Info,
     Warn,
@@ -41,6 +86,15 @@ impl std::fmt::Display for NotifierLogLevel {
     }
 }
 
+/// The color notifications of each level are highlighted with, as a hex RGB string
+pub(crate) fn get_notification_color(level: NotifierLogLevel) -> &'static str {
+    match level {
+        NotifierLogLevel::Info => "#3498DB",
+        NotifierLogLevel::Warn => "#FFC107",
+        NotifierLogLevel::Error => "#FF0000",
+    }
+}
+
 /// A type that can be logged
 pub trait Loggable {
     fn log_level(&self) -> NotifierLogLevel;
@@ -49,12 +103,12 @@ pub trait Loggable {
 /// A notifier that outputs messages on a channel
 #[async_trait]
 pub trait Notifier:
-    futures_core::Stream<Item = Result<PackedResource, BroadcastStreamRecvError>>
+    futures_core::Stream<Item = Result<ResourceUpdate, BroadcastStreamRecvError>>
 {
     type Notification: Send + Loggable;
 
-    /// Creates zero or more notifications based on a Kubernetes resource
-    fn create_notifications(&self, resource: &PackedResource) -> Vec<Self::Notification>;
+    /// Creates zero or more notifications based on an update to a Kubernetes resource
+    fn create_notifications(&self, update: &ResourceUpdate) -> Vec<Self::Notification>;
 
     /// Configured log level for this notifier
     fn log_level(&self) -> NotifierLogLevel;
@@ -68,8 +122,8 @@ pub trait Notifier:
         Self: Sized + Unpin + Send + Sync + 'static,
     {
         tokio::spawn(async move {
-            while let Some(resource) = self.next().await {
-                if let Err(e) = resource {
+            while let Some(update) = self.next().await {
+                if let Err(e) = update {
                     tracing::error!(
                         "Notifier failed to read from resource broadcast stream. Error: {:?}",
                         e
@@ -78,7 +132,7 @@ pub trait Notifier:
                 }
 
                 let notifications =
-                    self.create_notifications(&resource.expect("resource should be valid"));
+                    self.create_notifications(&update.expect("update should be valid"));
 
                 let results = future::join_all(
                     notifications
@@ -98,12 +152,12 @@ pub trait Notifier:
     }
 }
 
-/// Implements [`futures_core::Stream<Item = Result<PackedResource, BroadcastStreamRecvError>>`]
+/// Implements [`futures_core::Stream<Item = Result<ResourceUpdate, BroadcastStreamRecvError>>`]
 /// for the specified type. Requires that the second argument is a [`tokio_stream::wrappers::BroadcastStream`]
-macro_rules! impl_packed_resource_stream {
+macro_rules! impl_resource_update_stream {
     ($name:ident, $prop:ident) => {
         impl futures_core::Stream for $name {
-            type Item = Result<PackedResource, BroadcastStreamRecvError>;
+            type Item = Result<ResourceUpdate, BroadcastStreamRecvError>;
 
             fn poll_next(
                 mut self: std::pin::Pin<&mut Self>,
@@ -127,4 +181,4 @@ macro_rules! impl_loggable {
 }
 
 pub(crate) use impl_loggable;
-pub(crate) use impl_packed_resource_stream;
+pub(crate) use impl_resource_update_stream;
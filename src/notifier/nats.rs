@@ -0,0 +1,129 @@
+use async_nats::HeaderMap;
+use async_trait::async_trait;
+use futures::StreamExt;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+
+use super::content::{ContentBuilder, NotificationRecord};
+use super::{impl_loggable, impl_resource_update_stream, Loggable, Notifier, NotifierLogLevel};
+
+use crate::state::ResourceUpdate;
+
+/// Header carrying the `<namespace>/<name>` key of the object a message describes
+const KEY_HEADER: &str = "K8s-Notifier-Key";
+
+/// Notifier which publishes each notification as a JSON message to a NATS subject.
+///
+/// Messages are published to `<subject>.<namespace>.<name>` (or `<subject>.<name>` for
+/// cluster scoped objects), so that consumers and JetStream streams can filter by
+/// object and the messages of each object stay in order. Characters which aren't
+/// valid within a subject token, such as `.`, are replaced with `_`
+pub struct NatsNotifier {
+    rx: BroadcastStream<ResourceUpdate>,
+    client: async_nats::Client,
+    subject: String,
+    log_level: NotifierLogLevel,
+    content_builder: ContentBuilder,
+}
+
+impl NatsNotifier {
+    /// Connects to the NATS server at `url`, publishing to subjects under `subject`
+    pub async fn new(
+        rx: broadcast::Receiver<ResourceUpdate>,
+        url: String,
+        subject: String,
+        log_level: NotifierLogLevel,
+        cluster_name: String,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            rx: BroadcastStream::new(rx),
+            client: async_nats::connect(url).await?,
+            subject,
+            log_level,
+            content_builder: ContentBuilder::new(cluster_name),
+        })
+    }
+
+    fn subject_for(&self, record: &NotificationRecord) -> String {
+        let mut subject = self.subject.clone();
+        for token in record.namespace.iter().chain([&record.name]) {
+            subject.push('.');
+            subject.push_str(&subject_token(token));
+        }
+
+        subject
+    }
+}
+
+#[async_trait]
+impl Notifier for NatsNotifier {
+    type Notification = NatsNotification;
+
+    fn create_notifications(&self, update: &ResourceUpdate) -> Vec<Self::Notification> {
+        self.content_builder
+            .build(update)
+            .into_iter()
+            .filter_map(|content| {
+                let record =
+                    NotificationRecord::new(self.content_builder.cluster_name(), update, &content);
+
+                match serde_json::to_vec(&record) {
+                    Ok(payload) => Some(NatsNotification {
+                        level: content.level,
+                        subject: self.subject_for(&record),
+                        key: record.key(),
+                        payload,
+                    }),
+                    Err(e) => {
+                        tracing::error!("Failed to serialize nats notification: {e}");
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn log_level(&self) -> NotifierLogLevel {
+        self.log_level
+    }
+
+    async fn emit_notification(&self, notification: Self::Notification) -> anyhow::Result<()> {
+        let mut headers = HeaderMap::new();
+        headers.insert(KEY_HEADER, notification.key.as_str());
+
+        self.client
+            .publish_with_headers(
+                notification.subject.clone(),
+                headers,
+                notification.payload.into(),
+            )
+            .await?;
+        self.client.flush().await?;
+
+        tracing::info!(
+            "Published nats notification to subject {}",
+            notification.subject
+        );
+
+        Ok(())
+    }
+}
+
+impl_resource_update_stream!(NatsNotifier, rx);
+
+pub struct NatsNotification {
+    level: NotifierLogLevel,
+    subject: String,
+    key: String,
+    payload: Vec<u8>,
+}
+
+impl_loggable!(NatsNotification, level);
+
+/// Replaces characters which separate tokens or act as wildcards within subjects
+fn subject_token(s: &str) -> String {
+    s.replace(
+        |c: char| c == '.' || c == '*' || c == '>' || c.is_whitespace(),
+        "_",
+    )
+}
@@ -0,0 +1,193 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::json;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+
+use super::alert::{AlertAction, AlertTracker};
+use super::content::{ContentBuilder, NotificationContent};
+use super::{impl_loggable, impl_resource_update_stream, Loggable, Notifier, NotifierLogLevel};
+
+use crate::state::ResourceUpdate;
+
+/// The Opsgenie API base URL. Accounts in the EU region use `https://api.eu.opsgenie.com`
+pub const DEFAULT_API_URL: &str = "https://api.opsgenie.com";
+
+/// Opsgenie accepts at most 20 tags per alert, each at most 50 characters long
+const MAX_TAGS: usize = 20;
+const MAX_TAG_LENGTH: usize = 50;
+
+/// Opsgenie rejects alert messages longer than 130 characters
+const MAX_MESSAGE_LENGTH: usize = 130;
+
+/// Notifier which creates Opsgenie alerts, deduplicated by alias, and closes them once
+/// the resource recovers
+pub struct OpsgenieNotifier {
+    rx: BroadcastStream<ResourceUpdate>,
+    api_key: String,
+    api_url: reqwest::Url,
+    client: reqwest::Client,
+    /// The minimum level of content which triggers an alert
+    min_level: NotifierLogLevel,
+    content_builder: ContentBuilder,
+    alerts: AlertTracker,
+}
+
+impl OpsgenieNotifier {
+    pub fn new(
+        rx: broadcast::Receiver<ResourceUpdate>,
+        api_key: String,
+        api_url: reqwest::Url,
+        min_level: NotifierLogLevel,
+        cluster_name: String,
+    ) -> Self {
+        Self {
+            rx: BroadcastStream::new(rx),
+            api_key,
+            api_url,
+            client: reqwest::Client::new(),
+            min_level,
+            content_builder: ContentBuilder::new(cluster_name),
+            alerts: AlertTracker::default(),
+        }
+    }
+
+    /// Builds the URL of an alerts API endpoint, e.g. `/v2/alerts/<alias>/close`
+    fn alerts_url(&self, segments: &[&str]) -> reqwest::Url {
+        let mut url = self.api_url.clone();
+        url.path_segments_mut()
+            .expect("Opsgenie API URL should be an http(s) URL")
+            .pop_if_empty()
+            .extend(["v2", "alerts"])
+            .extend(segments);
+
+        url
+    }
+
+    fn create_alert(
+        &self,
+        alias: String,
+        update: &ResourceUpdate,
+        content: &NotificationContent,
+    ) -> OpsgenieNotification {
+        let title = content.plain_title();
+        let message = match title.char_indices().nth(MAX_MESSAGE_LENGTH) {
+            Some((i, _)) => title[..i].to_string(),
+            None => title.clone(),
+        };
+
+        let resource = &update.resource;
+        let mut tags = vec![
+            format!("cluster:{}", self.content_builder.cluster_name()),
+            format!("kind:{}", resource.kind()),
+        ];
+        if let Some(namespace) = resource.namespace() {
+            tags.push(format!("namespace:{namespace}"));
+        }
+        tags.extend(
+            resource
+                .labels()
+                .iter()
+                .map(|(key, val)| format!("{key}:{val}")),
+        );
+        tags.retain(|tag| tag.chars().count() <= MAX_TAG_LENGTH);
+        tags.truncate(MAX_TAGS);
+
+        // Details only accept string values
+        let details = content
+            .fields()
+            .into_iter()
+            .map(|field| (field.name, json!(field.value.to_plain_string())))
+            .collect::<serde_json::Map<_, _>>();
+
+        OpsgenieNotification {
+            url: self.alerts_url(&[]),
+            body: json!({
+                "message": message,
+                "alias": alias,
+                "description": title,
+                "tags": tags,
+                "details": details,
+                "entity": format!("{}/{}", resource.kind(), resource.name()),
+                "source": self.content_builder.cluster_name(),
+                "priority": get_priority(content.level),
+            }),
+            level: content.level,
+        }
+    }
+
+    fn close_alert(&self, alias: String) -> OpsgenieNotification {
+        let mut url = self.alerts_url(&[&alias, "close"]);
+        url.query_pairs_mut().append_pair("identifierType", "alias");
+
+        OpsgenieNotification {
+            url,
+            body: json!({
+                "source": self.content_builder.cluster_name(),
+                "note": "Resolved by k8s-notifier",
+            }),
+            level: self.min_level,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for OpsgenieNotifier {
+    type Notification = OpsgenieNotification;
+
+    fn create_notifications(&self, update: &ResourceUpdate) -> Vec<Self::Notification> {
+        let content = self.content_builder.build(update);
+
+        self.alerts
+            .observe(update, content.as_ref(), self.min_level)
+            .into_iter()
+            .filter_map(|action| match action {
+                AlertAction::Trigger { key } => content
+                    .as_ref()
+                    .map(|content| self.create_alert(key, update, content)),
+                AlertAction::Resolve { key } => Some(self.close_alert(key)),
+            })
+            .collect()
+    }
+
+    fn log_level(&self) -> NotifierLogLevel {
+        self.min_level
+    }
+
+    async fn emit_notification(&self, notification: Self::Notification) -> anyhow::Result<()> {
+        let res = self
+            .client
+            .post(notification.url)
+            .header("Authorization", format!("GenieKey {}", self.api_key))
+            .json(&notification.body)
+            .send()
+            .await?;
+
+        tracing::info!(
+            "Received status {} upon emitting opsgenie notification",
+            res.status()
+        );
+
+        res.error_for_status()?;
+
+        Ok(())
+    }
+}
+
+impl_resource_update_stream!(OpsgenieNotifier, rx);
+
+pub struct OpsgenieNotification {
+    level: NotifierLogLevel,
+    url: reqwest::Url,
+    body: serde_json::Value,
+}
+
+impl_loggable!(OpsgenieNotification, level);
+
+fn get_priority(level: NotifierLogLevel) -> &'static str {
+    match level {
+        NotifierLogLevel::Info => "P5",
+        NotifierLogLevel::Warn => "P3",
+        NotifierLogLevel::Error => "P1",
+    }
+}
@@ -0,0 +1,146 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::json;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+
+use super::alert::{get_severity, AlertAction, AlertTracker};
+use super::content::{ContentBuilder, NotificationContent};
+use super::{impl_loggable, impl_resource_update_stream, Loggable, Notifier, NotifierLogLevel};
+
+use crate::state::ResourceUpdate;
+
+/// The PagerDuty Events API v2 endpoint
+pub const DEFAULT_EVENTS_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/// Notifier which triggers PagerDuty incidents through the Events API v2, and resolves
+/// them once the resource recovers
+pub struct PagerDutyNotifier {
+    rx: BroadcastStream<ResourceUpdate>,
+    routing_key: String,
+    events_url: String,
+    client: reqwest::Client,
+    /// The minimum level of content which triggers an alert
+    min_level: NotifierLogLevel,
+    content_builder: ContentBuilder,
+    alerts: AlertTracker,
+}
+
+impl PagerDutyNotifier {
+    pub fn new(
+        rx: broadcast::Receiver<ResourceUpdate>,
+        routing_key: String,
+        events_url: String,
+        min_level: NotifierLogLevel,
+        cluster_name: String,
+    ) -> Self {
+        Self {
+            rx: BroadcastStream::new(rx),
+            routing_key,
+            events_url,
+            client: reqwest::Client::new(),
+            min_level,
+            content_builder: ContentBuilder::new(cluster_name),
+            alerts: AlertTracker::default(),
+        }
+    }
+
+    fn trigger_event(
+        &self,
+        key: String,
+        update: &ResourceUpdate,
+        content: &NotificationContent,
+    ) -> PagerDutyNotification {
+        let mut summary = content.plain_title();
+        // PagerDuty rejects summaries longer than 1024 characters
+        if let Some((i, _)) = summary.char_indices().nth(1024) {
+            summary.truncate(i);
+        }
+
+        let custom_details = content
+            .fields()
+            .into_iter()
+            .map(|field| (field.name, json!(field.value)))
+            .collect::<serde_json::Map<_, _>>();
+
+        PagerDutyNotification {
+            inner: json!({
+                "routing_key": self.routing_key,
+                "event_action": "trigger",
+                "dedup_key": key,
+                "payload": {
+                    "summary": summary,
+                    "source": self.content_builder.cluster_name(),
+                    "severity": get_severity(content.level),
+                    "component": format!("{}/{}", update.resource.kind(), update.resource.name()),
+                    "group": update.resource.namespace(),
+                    "class": content.reason,
+                    "custom_details": custom_details,
+                }
+            }),
+            level: content.level,
+        }
+    }
+
+    fn resolve_event(&self, key: String) -> PagerDutyNotification {
+        PagerDutyNotification {
+            inner: json!({
+                "routing_key": self.routing_key,
+                "event_action": "resolve",
+                "dedup_key": key,
+            }),
+            level: self.min_level,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for PagerDutyNotifier {
+    type Notification = PagerDutyNotification;
+
+    fn create_notifications(&self, update: &ResourceUpdate) -> Vec<Self::Notification> {
+        let content = self.content_builder.build(update);
+
+        self.alerts
+            .observe(update, content.as_ref(), self.min_level)
+            .into_iter()
+            .filter_map(|action| match action {
+                AlertAction::Trigger { key } => content
+                    .as_ref()
+                    .map(|content| self.trigger_event(key, update, content)),
+                AlertAction::Resolve { key } => Some(self.resolve_event(key)),
+            })
+            .collect()
+    }
+
+    fn log_level(&self) -> NotifierLogLevel {
+        self.min_level
+    }
+
+    async fn emit_notification(&self, notification: Self::Notification) -> anyhow::Result<()> {
+        let res = self
+            .client
+            .post(&self.events_url)
+            .json(&notification.inner)
+            .send()
+            .await?;
+
+        tracing::info!(
+            "Received status {} upon emitting pagerduty event",
+            res.status()
+        );
+
+        res.error_for_status()?;
+
+        Ok(())
+    }
+}
+
+impl_resource_update_stream!(PagerDutyNotifier, rx);
+
+pub struct PagerDutyNotification {
+    level: NotifierLogLevel,
+    inner: serde_json::Value,
+}
+
+impl_loggable!(PagerDutyNotification, level);
@@ -1,30 +1,230 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
+
 use async_trait::async_trait;
-use futures::StreamExt;
+use clap::ValueEnum;
+use futures::{future, StreamExt};
+use rand::Rng;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::Deserialize;
 use serde_json::json;
 use tokio::sync::broadcast;
 use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
 
-use super::{impl_loggable, impl_packed_resource_stream, Loggable, Notifier, NotifierLogLevel};
+use super::content::{ContentBuilder, Field, NotificationContent};
+use super::{
+    get_notification_color, impl_loggable, impl_resource_update_stream, Loggable, Notifier,
+    NotifierLogLevel,
+};
+
+use crate::logs::ContainerLogs;
+use crate::namespace::NamespacePattern;
+use crate::resource::{ChangeType, PackedResource};
+use crate::selector::{label_selector_matches, validate_label_selector};
+use crate::state::{ResourceUpdate, StateChange};
+
+/// The Slack Web API base URL
+pub const DEFAULT_API_URL: &str = "https://slack.com/api";
+
+/// How many times a Web API method is called before giving up on transient failures
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Bounds of the exponential backoff between attempts
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Errors reported by Slack which don't indicate a problem with the request itself
+const TRANSIENT_ERRORS: &[&str] = &[
+    "ratelimited",
+    "internal_error",
+    "fatal_error",
+    "service_unavailable",
+    "request_timeout",
+];
+
+/// Errors reported when a thread's parent message has been deleted
+const THREAD_GONE_ERRORS: &[&str] = &["message_not_found", "thread_not_found"];
+
+/// Where and how notifications are posted
+#[derive(Debug, Clone)]
+pub enum SlackTarget {
+    /// Post with `chat.postMessage` using a bot token. Notifications are threaded per
+    /// object, and posted to the channels of the routes they match or to
+    /// `default_channel` if no route claims them
+    Api {
+        token: String,
+        api_url: reqwest::Url,
+        default_channel: String,
+        routes: Vec<SlackRoute>,
+    },
+    /// Post to an incoming webhook, which is bound to a single channel. Incoming
+    /// webhooks can't reply in threads or edit messages, so every notification is
+    /// posted as a new message
+    Webhook { url: reqwest::Url },
+}
+
+/// Sends notifications matching all of its conditions to its channels. Parsed from `|`
+/// separated `<field>=<value>` pairs, e.g.
+/// `namespaces=payments-*|level=error|channels=C0123,C0456`, with the fields:
+///
+/// - `channels`: comma separated channel IDs to post to. Required
+/// - `namespaces`: comma separated namespace globs
+/// - `selector`: a label selector, e.g. `team=payments,tier in (web,api)`
+/// - `kinds`: comma separated resource kinds, e.g. `pod,deployment`
+/// - `level`: the minimum level of notifications to route
+/// - `continue`: consider later routes even if this one matches
+#[derive(Debug, Clone, Default)]
+pub struct SlackRoute {
+    pub channels: Vec<String>,
+    pub namespaces: Vec<NamespacePattern>,
+    pub selector: Option<String>,
+    pub kinds: Vec<String>,
+    pub level: Option<NotifierLogLevel>,
+    /// Whether later routes are also considered once this route matches
+    pub continue_matching: bool,
+}
+
+impl SlackRoute {
+    fn matches(&self, resource: &PackedResource, level: NotifierLogLevel) -> bool {
+        let namespace_matches = self.namespaces.is_empty()
+            || resource.namespace().is_some_and(|namespace| {
+                self.namespaces
+                    .iter()
+                    .any(|pattern| pattern.matches(&namespace))
+            });
+
+        let selector_matches = self
+            .selector
+            .as_ref()
+            .is_none_or(|selector| label_selector_matches(selector, resource.labels()));
+
+        let kind = resource.kind();
+        let kind_matches =
+            self.kinds.is_empty() || self.kinds.iter().any(|k| k.eq_ignore_ascii_case(&kind));
 
-use crate::resource::ext::event::EventExt;
-use crate::resource::ext::node::NodeExt;
-use crate::resource::ext::pod::PodExt;
-use crate::resource::PackedResource;
+        let level_matches = self.level.is_none_or(|min| level >= min);
 
+        namespace_matches && selector_matches && kind_matches && level_matches
+    }
+}
+
+impl FromStr for SlackRoute {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut route = SlackRoute::default();
+        let list = |value: &str| {
+            value
+                .split(',')
+                .map(str::trim)
+                .filter(|v| !v.is_empty())
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        };
+
+        for field in s.split('|').map(str::trim).filter(|f| !f.is_empty()) {
+            let (name, value) = field.split_once('=').unwrap_or((field, ""));
+            let value = value.trim();
+
+            match name.trim() {
+                "channels" => route.channels = list(value),
+                "namespaces" => {
+                    route.namespaces = list(value)
+                        .iter()
+                        .map(|pattern| pattern.parse())
+                        .collect::<Result<_, _>>()?
+                }
+                "selector" => {
+                    validate_label_selector(value)?;
+                    route.selector = Some(value.to_string());
+                }
+                "kinds" => route.kinds = list(value),
+                "level" => {
+                    route.level = Some(
+                        NotifierLogLevel::from_str(value, true)
+                            .map_err(|_| format!("invalid level '{value}' in slack route '{s}'"))?,
+                    )
+                }
+                "continue" if value.is_empty() || value == "true" => route.continue_matching = true,
+                "continue" if value == "false" => route.continue_matching = false,
+                _ => return Err(format!("invalid field '{field}' in slack route '{s}'")),
+            }
+        }
+
+        if route.channels.is_empty() {
+            return Err(format!("slack route '{s}' has no channels"));
+        }
+
+        Ok(route)
+    }
+}
+
+/// Notifier which posts messages to a Slack channel. When posting with a bot token, the
+/// first notification for an object starts a thread, to which later notifications for
+/// the object are replied. The thread's parent message is edited to reflect the
+/// object's latest state
 pub struct SlackNotifier {
-    rx: BroadcastStream<PackedResource>,
-    api_token: String,
-    channel_id: String,
+    rx: BroadcastStream<ResourceUpdate>,
+    target: SlackTarget,
     client: reqwest::Client,
     log_level: NotifierLogLevel,
-    cluster_name: String,
+    content_builder: ContentBuilder,
+    /// The parent message of each object's thread, keyed by the channel it was routed
+    /// to and the object's UID
+    threads: Mutex<HashMap<(String, String), SlackThread>>,
+}
+
+/// A failed request to Slack
+enum CallError {
+    /// The request may succeed if retried, after `retry_after` if Slack asked for a
+    /// delay
+    Transient {
+        error: anyhow::Error,
+        retry_after: Option<Duration>,
+    },
+    Fatal(anyhow::Error),
+}
+
+/// An error reported by a Web API method, e.g. `channel_not_found`
+#[derive(Debug)]
+struct ApiError {
+    method: String,
+    error: String,
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Slack method {} failed: {}", self.method, self.error)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+/// A message which started a thread
+#[derive(Debug, Clone)]
+struct SlackThread {
+    /// The ID of the channel the message was posted in, which `chat.update` requires
+    channel: String,
+    ts: String,
+}
+
+/// The subset of a Web API response needed to thread replies
+#[derive(Debug, Deserialize)]
+struct SlackResponse {
+    ok: bool,
+    error: Option<String>,
+    channel: Option<String>,
+    ts: Option<String>,
 }
 
 impl SlackNotifier {
     pub fn new(
-        rx: broadcast::Receiver<PackedResource>,
-        api_token: String,
-        channel_id: String,
+        rx: broadcast::Receiver<ResourceUpdate>,
+        target: SlackTarget,
         log_level: NotifierLogLevel,
         cluster_name: String,
     ) -> Self {
@@ -32,326 +232,333 @@ impl SlackNotifier {
 
         Self {
             rx: BroadcastStream::new(rx),
-            api_token,
-            channel_id,
+            target,
             client,
             log_level,
-            cluster_name,
+            content_builder: ContentBuilder::new(cluster_name),
+            threads: Mutex::default(),
         }
     }
-}
 
-#[async_trait]
-impl Notifier for SlackNotifier {
-    type Notification = SlackNotification;
+    /// The channels a notification is posted to. Routes are considered in order until
+    /// one which doesn't `continue` matches. The default channel receives the
+    /// notification unless such a route matched
+    fn route(&self, resource: &PackedResource, level: NotifierLogLevel) -> Vec<String> {
+        let SlackTarget::Api {
+            default_channel,
+            routes,
+            ..
+        } = &self.target
+        else {
+            return vec![];
+        };
 
-    // TODO(rkrishn7): Refactor this monstrosity
-    fn create_notifications(&self, resource: &PackedResource) -> Vec<Self::Notification> {
-        let mut notifications = vec![];
+        let mut channels = vec![];
+        let mut claimed = false;
 
-        match resource {
-            PackedResource::Node(node) => {
-                let labels_formatted = node
-                    .labels()
-                    .iter()
-                    .map(|(lbl, val)| format!("• `{lbl}` : `{val}`"))
-                    .collect::<Vec<String>>()
-                    .join("\n");
+        for route in routes.iter().filter(|route| route.matches(resource, level)) {
+            channels.extend(route.channels.iter().cloned());
 
-                let addresses_formatted = node
-                    .addresses()
-                    .unwrap_or_default()
-                    .iter()
-                    .map(|(typ, addr)| format!("• `{typ}` : `{addr}`"))
-                    .collect::<Vec<String>>()
-                    .join("\n");
+            if !route.continue_matching {
+                claimed = true;
+                break;
+            }
+        }
 
-                let conditions_formatted = node
-                    .status_conditions()
-                    .unwrap_or_default()
-                    .iter()
-                    .map(|(cond, val)| format!("• `{cond}` : `{val}`"))
-                    .collect::<Vec<String>>()
-                    .join("\n");
-
-                let labels_section = format!(
-                    "*Labels*\n{}",
-                    if labels_formatted.is_empty() {
-                        "<None>".to_string()
-                    } else {
-                        labels_formatted
-                    }
-                );
-                let addresses_section = format!(
-                    "*Addresses*\n{}",
-                    if addresses_formatted.is_empty() {
-                        "<None>".to_string()
-                    } else {
-                        addresses_formatted
-                    }
-                );
-                let conditions_section = format!(
-                    "*Conditions*\n{}",
-                    if conditions_formatted.is_empty() {
-                        "<None>".to_string()
-                    } else {
-                        conditions_formatted
-                    }
-                );
+        if !claimed {
+            channels.push(default_channel.clone());
+        }
 
-                let (title, log_level) = if node.unschedulable() {
-                    (
-                        format!(
-                            "Node `{}` is *unschedulable* in cluster `{}`",
-                            node.name(),
-                            self.cluster_name
-                        ),
-                        NotifierLogLevel::Error,
-                    )
-                } else {
-                    (
-                        format!(
-                            "Node `{}` is *healthy* in cluster `{}`",
-                            node.name(),
-                            self.cluster_name
-                        ),
-                        NotifierLogLevel::Info,
-                    )
-                };
-                let color = get_notification_color(log_level);
-
-                notifications.push(SlackNotification {
-                    inner: json!(
-                        {
-                        "channel": &self.channel_id,
-                        "attachments": [
-                            {
-                                "color": color,
-                                "blocks": [
-                                    {
-                                        "type": "section",
-                                        "text": {
-                                            "type": "mrkdwn",
-                                            "text": title,
-                                        }
-                                    },
-                                    {
-                                        "type": "divider"
-                                    },
-                                    {
-                                        "type": "section",
-                                        "fields": [
-                                            {
-                                                "type": "mrkdwn",
-                                                "text": conditions_section
-                                            },
-                                            {
-                                                "type": "mrkdwn",
-                                                "text": addresses_section
-                                            },
-                                        ]
-                                    },
-                                    {
-                                        "type": "section",
-                                        "fields": [
-                                            {
-                                                "type": "mrkdwn",
-                                                "text": labels_section
-                                            },
-                                        ]
-                                    },
-                                ]
-                            }
-                        ]
-                    }),
-                    level: log_level,
-                });
-            }
-            PackedResource::Pod(pod) => {
-                if let Some(phase) = pod.phase() {
-                    let labels_formatted = pod
-                        .labels()
-                        .iter()
-                        .map(|(lbl, val)| format!("• `{lbl}` : `{val}`"))
-                        .collect::<Vec<String>>()
-                        .join("\n");
-
-                    let labels_section = format!(
-                        "*Labels*\n{}",
-                        if labels_formatted.is_empty() {
-                            "<None>".to_string()
-                        } else {
-                            labels_formatted
-                        }
-                    );
+        let mut seen = HashSet::new();
+        channels.retain(|channel| seen.insert(channel.clone()));
 
-                    let name = pod.name();
-                    let namespace_section = format!(
-                        "*Namespace*\n`{}`",
-                        pod.namespace().unwrap_or("<Unknown>".to_string())
-                    );
-                    let ip_section = format!(
-                        "*IP Address*\n`{}`",
-                        pod.ip_addr().unwrap_or(&"<None>".to_string())
-                    );
-                    let title = format!(
-                        "Pod `{name}` is in phase *{phase}* in cluster `{}`",
-                        self.cluster_name
-                    );
+        channels
+    }
+
+    /// Builds a notification for its routed channels. The title is rendered in its
+    /// own section, followed by a section describing what changed since the resource
+    /// was last observed (if anything), one section per row with its fields side by
+    /// side and finally one section per attached container's logs
+    fn build_notification(
+        &self,
+        update: &ResourceUpdate,
+        content: NotificationContent,
+    ) -> SlackNotification {
+        let NotificationContent {
+            title,
+            level,
+            rows,
+            changes,
+            logs,
+            ..
+        } = content;
+
+        let mut blocks = vec![
+            json!({
+                "type": "section",
+                "text": {
+                    "type": "mrkdwn",
+                    "text": title,
+                }
+            }),
+            json!({
+                "type": "divider"
+            }),
+        ];
 
-                    let log_level = if phase == "Running" || phase == "Succeeded" {
-                        NotifierLogLevel::Info
-                    } else if phase == "Pending" {
-                        NotifierLogLevel::Warn
-                    } else {
-                        NotifierLogLevel::Error
-                    };
-                    let color = get_notification_color(log_level);
-
-                    notifications.push(SlackNotification {
-                        inner: json!(
-                            {
-                            "channel": &self.channel_id,
-                            "attachments": [
-                                {
-                                    "color": color,
-                                    "blocks": [
-                                        {
-                                            "type": "section",
-                                            "text": {
-                                                "type": "mrkdwn",
-                                                "text": title,
-                                            }
-                                        },
-                                        {
-                                            "type": "divider"
-                                        },
-                                        {
-                                            "type": "section",
-                                            "fields": [
-                                                {
-                                                    "type": "mrkdwn",
-                                                    "text": namespace_section
-                                                },
-                                                {
-                                                    "type": "mrkdwn",
-                                                    "text": ip_section
-                                                },
-                                            ]
-                                        },
-                                        {
-                                            "type": "section",
-                                            "fields": [
-                                                {
-                                                    "type": "mrkdwn",
-                                                    "text": labels_section
-                                                },
-                                            ]
-                                        },
-                                    ]
-                                }
-                            ]
-                        }),
-                        level: log_level,
-                    });
+        if !changes.is_empty() {
+            blocks.push(json!({
+                "type": "section",
+                "text": {
+                    "type": "mrkdwn",
+                    "text": format_changes_section(&changes),
                 }
+            }));
+        }
+
+        blocks.extend(rows.into_iter().map(|fields| {
+            json!({
+                "type": "section",
+                "fields": fields
+                    .into_iter()
+                    .map(|field| json!({ "type": "mrkdwn", "text": format_field(&field) }))
+                    .collect::<Vec<_>>(),
+            })
+        }));
+
+        blocks.extend(logs.iter().map(|logs| {
+            json!({
+                "type": "section",
+                "text": {
+                    "type": "mrkdwn",
+                    "text": format_logs_section(logs),
+                }
+            })
+        }));
+
+        let resource = &update.resource;
+
+        SlackNotification {
+            attachments: json!([
+                {
+                    "color": get_notification_color(level),
+                    "blocks": blocks,
+                }
+            ]),
+            channels: self.route(resource, level),
+            thread_key: thread_key(resource),
+            closes_thread: update.change == ChangeType::Deleted,
+            level,
+        }
+    }
+
+    /// Calls a Slack Web API method, failing if Slack reports an error
+    async fn call(
+        &self,
+        token: &str,
+        api_url: &reqwest::Url,
+        method: &str,
+        body: serde_json::Value,
+    ) -> anyhow::Result<SlackResponse> {
+        let mut url = api_url.clone();
+        url.path_segments_mut()
+            .expect("Slack API URL should be an http(s) URL")
+            .pop_if_empty()
+            .push(method);
+
+        with_retries(&format!("Slack method {method}"), || async {
+            let res = self
+                .client
+                .post(url.clone())
+                .bearer_auth(token)
+                .json(&body)
+                .send()
+                .await
+                .map_err(request_error)?;
+
+            tracing::info!(
+                "Received status {} upon calling slack method {method}",
+                res.status()
+            );
+
+            check_status(res.status(), res.headers(), method)?;
+
+            let response = res
+                .json::<SlackResponse>()
+                .await
+                .map_err(|e| CallError::Fatal(e.into()))?;
+
+            if !response.ok {
+                let error = response.error.as_deref().unwrap_or("unknown_error");
+                return Err(api_error(method, error));
             }
-            PackedResource::Event(event) => {
-                if let Some(typ) = event.typ() {
-                    let log_level = if typ == "Normal" {
-                        NotifierLogLevel::Info
-                    } else {
-                        NotifierLogLevel::Warn
-                    };
-                    let color = get_notification_color(log_level);
-                    let title = format!(
-                        "*{}* events seen from the {} `{}`",
-                        event.count().unwrap_or(0),
-                        event
-                            .involved_object_kind()
-                            .unwrap_or(&"<Unknown Resource>".to_string()),
-                        event
-                            .involved_object_name()
-                            .unwrap_or(&"<Unknown Name>".to_string())
-                    );
 
-                    let first_seen_section = format!(
-                        "*First Seen*\n`{}`",
-                        event
-                            .first_timestamp()
-                            .map(|t| t.to_string())
-                            .unwrap_or("<Unknown>".to_string())
-                    );
-                    let last_seen_section = format!(
-                        "*Last Seen*\n`{}`",
-                        event
-                            .last_timestamp()
-                            .map(|t| t.to_string())
-                            .unwrap_or("<Unknown>".to_string())
-                    );
-                    let message_section = format!(
-                        "*Message*\n{}",
-                        event.message().unwrap_or(&"\"\"".to_string())
-                    );
-                    let reason_section = format!(
-                        "*Reason*\n{}",
-                        event.reason().unwrap_or(&"\"\"".to_string())
+            Ok(response)
+        })
+        .await
+    }
+
+    /// Posts a message to an incoming webhook, which replies with a plain text error
+    /// (e.g. `invalid_payload`) rather than JSON when it fails
+    async fn post_webhook(
+        &self,
+        url: &reqwest::Url,
+        body: serde_json::Value,
+    ) -> anyhow::Result<()> {
+        with_retries("Slack webhook", || async {
+            let res = self
+                .client
+                .post(url.clone())
+                .json(&body)
+                .send()
+                .await
+                .map_err(request_error)?;
+
+            tracing::info!(
+                "Received status {} upon emitting slack notification",
+                res.status()
+            );
+
+            check_status(res.status(), res.headers(), "webhook")?;
+            if !res.status().is_success() {
+                let status = res.status();
+                let error = res.text().await.unwrap_or_default();
+
+                return Err(CallError::Fatal(anyhow::anyhow!(
+                    "Slack webhook failed with status {status}: {error}"
+                )));
+            }
+
+            Ok(())
+        })
+        .await
+    }
+
+    /// Posts the notification as a reply in its object's thread, then edits the
+    /// thread's parent to match. Starts a new thread if the object doesn't have one,
+    /// or its parent message has since been deleted
+    async fn post_threaded(
+        &self,
+        token: &str,
+        api_url: &reqwest::Url,
+        channel_id: &str,
+        notification: &SlackNotification,
+    ) -> anyhow::Result<()> {
+        let thread_key = (channel_id.to_string(), notification.thread_key.clone());
+        let thread = self
+            .threads
+            .lock()
+            .expect("slack threads lock poisoned")
+            .get(&thread_key)
+            .cloned();
+
+        if let Some(thread) = thread {
+            match self.reply(token, api_url, &thread, notification).await {
+                Err(e) if is_thread_gone(&e) => {
+                    tracing::warn!(
+                        "Slack thread of {} in channel {channel_id} no longer exists, starting a new one",
+                        notification.thread_key
                     );
 
-                    notifications.push(SlackNotification {
-                        inner: json!(
-                            {
-                            "channel": &self.channel_id,
-                            "attachments": [
-                                {
-                                    "color": color,
-                                    "blocks": [
-                                        {
-                                            "type": "section",
-                                            "text": {
-                                                "type": "mrkdwn",
-                                                "text": title,
-                                            }
-                                        },
-                                        {
-                                            "type": "divider"
-                                        },
-                                        {
-                                            "type": "section",
-                                            "fields": [
-                                                {
-                                                    "type": "mrkdwn",
-                                                    "text": first_seen_section
-                                                },
-                                                {
-                                                    "type": "mrkdwn",
-                                                    "text": last_seen_section
-                                                },
-                                            ]
-                                        },
-                                        {
-                                            "type": "section",
-                                            "fields": [
-                                                {
-                                                    "type": "mrkdwn",
-                                                    "text": reason_section
-                                                },
-                                                {
-                                                    "type": "mrkdwn",
-                                                    "text": message_section
-                                                },
-                                            ]
-                                        },
-                                    ]
-                                }
-                            ]
-                        }),
-                        level: log_level,
-                    });
+                    self.threads
+                        .lock()
+                        .expect("slack threads lock poisoned")
+                        .remove(&thread_key);
                 }
+                result => return result,
             }
         }
 
+        let response = self
+            .call(
+                token,
+                api_url,
+                "chat.postMessage",
+                json!({
+                    "channel": channel_id,
+                    "attachments": &notification.attachments,
+                }),
+            )
+            .await?;
+
+        if notification.closes_thread {
+            return Ok(());
+        }
+
+        if let (Some(channel), Some(ts)) = (response.channel, response.ts) {
+            self.threads
+                .lock()
+                .expect("slack threads lock poisoned")
+                .insert(thread_key, SlackThread { channel, ts });
+        }
+
+        Ok(())
+    }
+
+    /// Replies to `thread` with the notification, then edits its parent to match
+    async fn reply(
+        &self,
+        token: &str,
+        api_url: &reqwest::Url,
+        thread: &SlackThread,
+        notification: &SlackNotification,
+    ) -> anyhow::Result<()> {
+        self.call(
+            token,
+            api_url,
+            "chat.postMessage",
+            json!({
+                "channel": &thread.channel,
+                "thread_ts": &thread.ts,
+                "attachments": &notification.attachments,
+            }),
+        )
+        .await?;
+
+        self.call(
+            token,
+            api_url,
+            "chat.update",
+            json!({
+                "channel": &thread.channel,
+                "ts": &thread.ts,
+                "attachments": &notification.attachments,
+            }),
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Forgets the threads of an object in every channel, once it has been deleted
+    fn forget_threads(&self, thread_key: &str) {
+        self.threads
+            .lock()
+            .expect("slack threads lock poisoned")
+            .retain(|(_, key), _| key != thread_key);
+    }
+}
+
+#[async_trait]
+impl Notifier for SlackNotifier {
+    type Notification = SlackNotification;
+
+    fn create_notifications(&self, update: &ResourceUpdate) -> Vec<Self::Notification> {
+        let notifications = self
+            .content_builder
+            .build(update)
+            .into_iter()
+            .map(|content| self.build_notification(update, content))
+            .collect::<Vec<_>>();
+
+        // Deletions which aren't posted (e.g. because they're below the log level) would
+        // otherwise leave the object's threads behind forever
+        if update.change == ChangeType::Deleted
+            && notifications.iter().all(|n| n.level < self.log_level)
+        {
+            self.forget_threads(&thread_key(&update.resource));
+        }
+
         notifications
     }
 
@@ -360,36 +567,464 @@ impl Notifier for SlackNotifier {
     }
 
     async fn emit_notification(&self, notification: Self::Notification) -> anyhow::Result<()> {
-        let res = self
-            .client
-            .post("https://slack.com/api/chat.postMessage")
-            .bearer_auth(&self.api_token)
-            .json(&notification.inner)
-            .send()
-            .await?;
+        match &self.target {
+            SlackTarget::Api { token, api_url, .. } => {
+                let result = future::join_all(
+                    notification
+                        .channels
+                        .iter()
+                        .map(|channel| self.post_threaded(token, api_url, channel, &notification)),
+                )
+                .await
+                .into_iter()
+                .collect();
 
-        tracing::info!(
-            "Received status {} upon emitting slack notification",
-            res.status()
-        );
+                if notification.closes_thread {
+                    self.forget_threads(&notification.thread_key);
+                }
 
-        Ok(())
+                result
+            }
+            SlackTarget::Webhook { url } => {
+                self.post_webhook(url, json!({ "attachments": notification.attachments }))
+                    .await
+            }
+        }
     }
 }
 
-impl_packed_resource_stream!(SlackNotifier, rx);
+impl_resource_update_stream!(SlackNotifier, rx);
 
 pub struct SlackNotification {
     level: NotifierLogLevel,
-    inner: serde_json::Value,
+    attachments: serde_json::Value,
+    /// The channels the notification is routed to. Empty when posting to a webhook
+    channels: Vec<String>,
+    /// Identifies the object the notification is about, so that notifications for the
+    /// same object are threaded
+    thread_key: String,
+    /// Whether the object was deleted, after which its thread is forgotten
+    closes_thread: bool,
 }
 
 impl_loggable!(SlackNotification, level);
 
-fn get_notification_color(level: NotifierLogLevel) -> &'static str {
-    match level {
-        NotifierLogLevel::Info => "#3498DB",
-        NotifierLogLevel::Warn => "#FFC107",
-        NotifierLogLevel::Error => "#FF0000",
+/// Identifies the object a notification is about, so that notifications for the same
+/// object are threaded
+fn thread_key(resource: &PackedResource) -> String {
+    resource.uid().unwrap_or_else(|| {
+        format!(
+            "{}/{}/{}",
+            resource.kind(),
+            resource.namespace().unwrap_or_default(),
+            resource.name()
+        )
+    })
+}
+
+/// Whether a call failed because the thread it was made in no longer exists
+fn is_thread_gone(e: &anyhow::Error) -> bool {
+    e.downcast_ref::<ApiError>()
+        .is_some_and(|e| THREAD_GONE_ERRORS.contains(&e.error.as_str()))
+}
+
+/// Runs `request` until it succeeds or fails fatally, giving up after [`MAX_ATTEMPTS`].
+/// Transient failures are retried with jittered exponential backoff, or after the
+/// delay Slack asks for when rate limiting
+async fn with_retries<T, F, Fut>(description: &str, mut request: F) -> anyhow::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, CallError>>,
+{
+    let mut attempt = 1;
+
+    loop {
+        match request().await {
+            Ok(response) => return Ok(response),
+            Err(CallError::Transient { error, retry_after }) if attempt < MAX_ATTEMPTS => {
+                let delay = retry_after.unwrap_or_else(|| backoff(attempt));
+                tracing::warn!(
+                    "{description} failed on attempt {attempt}, retrying in {delay:?}. Error: {error:?}"
+                );
+
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            Err(CallError::Transient { error, .. } | CallError::Fatal(error)) => return Err(error),
+        }
+    }
+}
+
+/// Requests which couldn't be sent are worth retrying, unless they couldn't be built
+fn request_error(e: reqwest::Error) -> CallError {
+    if e.is_builder() {
+        CallError::Fatal(e.into())
+    } else {
+        CallError::Transient {
+            error: e.into(),
+            retry_after: None,
+        }
+    }
+}
+
+/// Classifies an error reported by a Web API method as transient or fatal
+fn api_error(method: &str, error: &str) -> CallError {
+    let message = anyhow::Error::new(ApiError {
+        method: method.to_string(),
+        error: error.to_string(),
+    });
+
+    if TRANSIENT_ERRORS.contains(&error) {
+        CallError::Transient {
+            error: message,
+            retry_after: None,
+        }
+    } else {
+        CallError::Fatal(message)
+    }
+}
+
+/// Fails on rate limiting and server errors, which are worth retrying. Other
+/// responses are left for the caller to interpret. Rate limiting for longer than
+/// [`MAX_BACKOFF`] isn't retried, so that a single notification can't hold up the
+/// notifier for long
+fn check_status(status: StatusCode, headers: &HeaderMap, target: &str) -> Result<(), CallError> {
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        let retry_after = headers
+            .get(RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse().ok())
+            .map(Duration::from_secs);
+
+        if let Some(retry_after) = retry_after.filter(|delay| *delay > MAX_BACKOFF) {
+            return Err(CallError::Fatal(anyhow::anyhow!(
+                "Slack {target} was rate limited for {}s, longer than retries wait for",
+                retry_after.as_secs()
+            )));
+        }
+
+        return Err(CallError::Transient {
+            error: anyhow::anyhow!("Slack {target} was rate limited"),
+            retry_after,
+        });
+    }
+
+    if status.is_server_error() {
+        return Err(CallError::Transient {
+            error: anyhow::anyhow!("Slack {target} failed with status {status}"),
+            retry_after: None,
+        });
+    }
+
+    Ok(())
+}
+
+/// The delay before retrying after `attempt` failed attempts. Doubles with each attempt
+/// up to [`MAX_BACKOFF`], randomized within its upper half so that concurrent retries
+/// spread out
+fn backoff(attempt: u32) -> Duration {
+    let max = BASE_BACKOFF
+        .saturating_mul(2u32.saturating_pow(attempt - 1))
+        .min(MAX_BACKOFF);
+
+    rand::thread_rng().gen_range(max / 2..=max)
+}
+
+/// Formats a field as a titled section, e.g.
+///
+/// ```text
+/// *Labels*
+/// • `app` : `nginx`
+/// ```
+fn format_field(field: &Field) -> String {
+    format!(
+        "*{}*
+{}",
+        field.name,
+        field.value.to_markdown()
+    )
+}
+
+/// Formats a container's logs as a code block
+fn format_logs_section(logs: &ContainerLogs) -> String {
+    let run = if logs.previous { "previous" } else { "current" };
+    let truncated = if logs.truncated { " (truncated)" } else { "" };
+    let content = if logs.content.trim().is_empty() {
+        "<No output>"
+    } else {
+        logs.content.trim_end()
+    };
+
+    format!(
+        "*Logs of `{}` from its {run} run{truncated}*\n```{content}```",
+        logs.container
+    )
+}
+
+/// Formats a section listing each changed field in the form:
+///
+/// ```text
+/// *Changes*
+/// • `Phase` : `Pending` → `Running`
+/// ```
+fn format_changes_section(changes: &[StateChange]) -> String {
+    let formatted = changes
+        .iter()
+        .map(|change| {
+            format!(
+                "• `{}` : `{}` → `{}`",
+                change.field,
+                change.previous.as_deref().unwrap_or("<None>"),
+                change.current.as_deref().unwrap_or("<None>")
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    format!("*Changes*\n{formatted}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use k8s_openapi::api::core::v1::Pod;
+    use kube::api::ObjectMeta;
+    use reqwest::header::HeaderValue;
+
+    use super::*;
+
+    /// A transient error which is retried immediately
+    fn transient() -> CallError {
+        CallError::Transient {
+            error: anyhow::anyhow!("transient"),
+            retry_after: Some(Duration::ZERO),
+        }
+    }
+
+    #[test]
+    fn classifies_api_errors() {
+        for error in TRANSIENT_ERRORS {
+            assert!(matches!(
+                api_error("chat.postMessage", error),
+                CallError::Transient { .. }
+            ));
+        }
+
+        for error in ["channel_not_found", "invalid_auth", "message_not_found"] {
+            assert!(matches!(
+                api_error("chat.postMessage", error),
+                CallError::Fatal(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn classifies_statuses() {
+        let headers = HeaderMap::new();
+
+        assert!(check_status(StatusCode::OK, &headers, "webhook").is_ok());
+        assert!(check_status(StatusCode::BAD_REQUEST, &headers, "webhook").is_ok());
+        assert!(matches!(
+            check_status(StatusCode::BAD_GATEWAY, &headers, "webhook"),
+            Err(CallError::Transient {
+                retry_after: None,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn honors_retry_after_up_to_max_backoff() {
+        let check = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+
+            check_status(StatusCode::TOO_MANY_REQUESTS, &headers, "webhook")
+        };
+        let retry_after = |value: &str| match check(value) {
+            Err(CallError::Transient { retry_after, .. }) => retry_after,
+            _ => panic!("rate limiting for '{value}' should be transient"),
+        };
+
+        assert_eq!(retry_after("3"), Some(Duration::from_secs(3)));
+        assert_eq!(retry_after("30"), Some(MAX_BACKOFF));
+        assert_eq!(retry_after("soon"), None);
+        assert!(matches!(check("3600"), Err(CallError::Fatal(_))));
+    }
+
+    #[test]
+    fn backoff_doubles_within_bounds() {
+        for attempt in 1..=MAX_ATTEMPTS + 10 {
+            let max = BASE_BACKOFF
+                .saturating_mul(2u32.saturating_pow(attempt - 1))
+                .min(MAX_BACKOFF);
+            let delay = backoff(attempt);
+
+            assert!(delay >= max / 2 && delay <= max, "{attempt}: {delay:?}");
+        }
+
+        assert!(backoff(1) <= BASE_BACKOFF);
+        assert!(backoff(100) <= MAX_BACKOFF);
+    }
+
+    #[tokio::test]
+    async fn retries_transient_failures_until_success() {
+        let attempts = AtomicU32::new(0);
+
+        let result = with_retries("test", || async {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(transient())
+            } else {
+                Ok("done")
+            }
+        })
+        .await;
+
+        assert_eq!(result.unwrap(), "done");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_max_attempts() {
+        let attempts = AtomicU32::new(0);
+
+        let result = with_retries("test", || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(transient())
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), MAX_ATTEMPTS);
+    }
+
+    #[tokio::test]
+    async fn does_not_retry_fatal_failures() {
+        let attempts = AtomicU32::new(0);
+
+        let result = with_retries("test", || async {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err::<(), _>(CallError::Fatal(anyhow::anyhow!("fatal")))
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    fn pod(namespace: &str, labels: &[(&str, &str)]) -> PackedResource {
+        PackedResource::Pod(Pod {
+            metadata: ObjectMeta {
+                name: Some("web-0".to_string()),
+                namespace: Some(namespace.to_string()),
+                labels: Some(
+                    labels
+                        .iter()
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .collect(),
+                ),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+    }
+
+    fn notifier(routes: &[&str]) -> SlackNotifier {
+        let (_, rx) = broadcast::channel(1);
+
+        SlackNotifier::new(
+            rx,
+            SlackTarget::Api {
+                token: "xoxb-test".to_string(),
+                api_url: DEFAULT_API_URL.parse().unwrap(),
+                default_channel: "CDEFAULT".to_string(),
+                routes: routes.iter().map(|route| route.parse().unwrap()).collect(),
+            },
+            NotifierLogLevel::Info,
+            "test".to_string(),
+        )
+    }
+
+    #[test]
+    fn parses_routes() {
+        let route: SlackRoute =
+            "channels=C1, C2|namespaces=payments-*|selector=team=payments|kinds=pod,deployment|level=warn"
+                .parse()
+                .unwrap();
+
+        assert_eq!(route.channels, ["C1", "C2"]);
+        assert_eq!(route.namespaces.len(), 1);
+        assert_eq!(route.selector.as_deref(), Some("team=payments"));
+        assert_eq!(route.kinds, ["pod", "deployment"]);
+        assert_eq!(route.level, Some(NotifierLogLevel::Warn));
+        assert!(!route.continue_matching);
+
+        let route: SlackRoute = "channels=C1|continue".parse().unwrap();
+        assert!(route.continue_matching);
+
+        let route: SlackRoute = "channels=C1|continue=false".parse().unwrap();
+        assert!(!route.continue_matching);
+    }
+
+    #[test]
+    fn rejects_invalid_routes() {
+        for route in [
+            "",
+            "namespaces=payments",
+            "channels=",
+            "channels=C1|level=loud",
+            "channels=C1|selector=team in payments",
+            "channels=C1|continue=maybe",
+            "channels=C1|owner=payments",
+        ] {
+            assert!(route.parse::<SlackRoute>().is_err(), "{route}");
+        }
+    }
+
+    #[test]
+    fn routes_to_first_matching_route() {
+        let notifier = notifier(&[
+            "channels=CPAYMENTS|namespaces=payments-*",
+            "channels=CTEAM|selector=team=payments",
+        ]);
+
+        let resource = pod("payments-prod", &[("team", "payments")]);
+        assert_eq!(
+            notifier.route(&resource, NotifierLogLevel::Error),
+            ["CPAYMENTS"]
+        );
+    }
+
+    #[test]
+    fn routes_continue_to_later_routes() {
+        let notifier = notifier(&[
+            "channels=CAUDIT,CERRORS|level=error|continue",
+            "channels=CPAYMENTS,CERRORS|namespaces=payments-*",
+        ]);
+
+        let resource = pod("payments-prod", &[]);
+        assert_eq!(
+            notifier.route(&resource, NotifierLogLevel::Error),
+            ["CAUDIT", "CERRORS", "CPAYMENTS"]
+        );
+        assert_eq!(
+            notifier.route(&resource, NotifierLogLevel::Info),
+            ["CPAYMENTS", "CERRORS"]
+        );
+    }
+
+    #[test]
+    fn routes_unclaimed_notifications_to_default_channel() {
+        let notifier = notifier(&[
+            "channels=CAUDIT|continue",
+            "channels=CPAYMENTS|namespaces=payments-*",
+            "channels=CNODES|kinds=node",
+        ]);
+
+        assert_eq!(
+            notifier.route(&pod("default", &[]), NotifierLogLevel::Info),
+            ["CAUDIT", "CDEFAULT"]
+        );
     }
 }
@@ -0,0 +1,221 @@
+use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::json;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+
+use super::content::{ContentBuilder, Field, FieldValue, NotificationContent};
+use super::{impl_loggable, impl_resource_update_stream, Loggable, Notifier, NotifierLogLevel};
+
+use crate::state::ResourceUpdate;
+
+/// Notifier which posts Adaptive Cards to a Microsoft Teams incoming webhook
+pub struct TeamsNotifier {
+    rx: BroadcastStream<ResourceUpdate>,
+    webhook_url: reqwest::Url,
+    client: reqwest::Client,
+    log_level: NotifierLogLevel,
+    content_builder: ContentBuilder,
+}
+
+impl TeamsNotifier {
+    pub fn new(
+        rx: broadcast::Receiver<ResourceUpdate>,
+        webhook_url: reqwest::Url,
+        log_level: NotifierLogLevel,
+        cluster_name: String,
+    ) -> Self {
+        Self {
+            rx: BroadcastStream::new(rx),
+            webhook_url,
+            client: reqwest::Client::new(),
+            log_level,
+            content_builder: ContentBuilder::new(cluster_name),
+        }
+    }
+
+    /// Builds an Adaptive Card from `content`. The title is rendered in a container
+    /// styled by severity, followed by the changes since the resource was last observed
+    /// (if any), one column set per row of fields and finally any attached logs
+    fn build_notification(&self, content: NotificationContent) -> TeamsNotification {
+        // Adaptive Cards support bold text but not inline code
+        let title = content.markdown_title().replace('`', "");
+
+        let mut body = vec![json!({
+            "type": "Container",
+            "style": get_container_style(content.level),
+            "bleed": true,
+            "items": [
+                {
+                    "type": "TextBlock",
+                    "text": title,
+                    "wrap": true,
+                    "size": "Medium",
+                }
+            ]
+        })];
+
+        if !content.changes.is_empty() {
+            body.push(json!({
+                "type": "TextBlock",
+                "text": "Changes",
+                "weight": "Bolder",
+                "separator": true,
+            }));
+            body.push(json!({
+                "type": "FactSet",
+                "facts": content
+                    .changes
+                    .iter()
+                    .map(|change| json!({
+                        "title": change.field,
+                        "value": format!(
+                            "{} → {}",
+                            change.previous.as_deref().unwrap_or("<None>"),
+                            change.current.as_deref().unwrap_or("<None>")
+                        ),
+                    }))
+                    .collect::<Vec<_>>(),
+            }));
+        }
+
+        body.extend(content.rows.iter().map(|fields| {
+            json!({
+                "type": "ColumnSet",
+                "separator": true,
+                "columns": fields
+                    .iter()
+                    .map(|field| json!({
+                        "type": "Column",
+                        "width": "stretch",
+                        "items": field_items(field),
+                    }))
+                    .collect::<Vec<_>>(),
+            })
+        }));
+
+        for logs in &content.logs {
+            body.push(json!({
+                "type": "TextBlock",
+                "text": format!("Logs of {}", logs.container),
+                "weight": "Bolder",
+                "separator": true,
+            }));
+            body.push(json!({
+                "type": "TextBlock",
+                "text": logs.content,
+                "fontType": "Monospace",
+                "wrap": true,
+            }));
+        }
+
+        TeamsNotification {
+            inner: json!({
+                "type": "message",
+                "attachments": [
+                    {
+                        "contentType": "application/vnd.microsoft.card.adaptive",
+                        "content": {
+                            "$schema": "http://adaptivecards.io/schemas/adaptive-card.json",
+                            "type": "AdaptiveCard",
+                            "version": "1.4",
+                            "msteams": { "width": "Full" },
+                            "body": body,
+                        }
+                    }
+                ]
+            }),
+            level: content.level,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for TeamsNotifier {
+    type Notification = TeamsNotification;
+
+    fn create_notifications(&self, update: &ResourceUpdate) -> Vec<Self::Notification> {
+        self.content_builder
+            .build(update)
+            .into_iter()
+            .map(|content| self.build_notification(content))
+            .collect()
+    }
+
+    fn log_level(&self) -> NotifierLogLevel {
+        self.log_level
+    }
+
+    async fn emit_notification(&self, notification: Self::Notification) -> anyhow::Result<()> {
+        let res = self
+            .client
+            .post(self.webhook_url.clone())
+            .json(&notification.inner)
+            .send()
+            .await?;
+
+        tracing::info!(
+            "Received status {} upon emitting teams notification",
+            res.status()
+        );
+
+        res.error_for_status()?;
+
+        Ok(())
+    }
+}
+
+impl_resource_update_stream!(TeamsNotifier, rx);
+
+pub struct TeamsNotification {
+    level: NotifierLogLevel,
+    inner: serde_json::Value,
+}
+
+impl_loggable!(TeamsNotification, level);
+
+/// The container style used to color a notification's title by severity
+fn get_container_style(level: NotifierLogLevel) -> &'static str {
+    match level {
+        NotifierLogLevel::Info => "accent",
+        NotifierLogLevel::Warn => "warning",
+        NotifierLogLevel::Error => "attention",
+    }
+}
+
+/// Renders a field as a bold heading followed by its value. Entries are rendered as
+/// a fact set
+fn field_items(field: &Field) -> Vec<serde_json::Value> {
+    let heading = json!({
+        "type": "TextBlock",
+        "text": field.name,
+        "weight": "Bolder",
+    });
+
+    let value = match &field.value {
+        FieldValue::Code(value) => json!({
+            "type": "TextBlock",
+            "text": value,
+            "fontType": "Monospace",
+            "wrap": true,
+        }),
+        FieldValue::Text(value) => json!({
+            "type": "TextBlock",
+            "text": value,
+            "wrap": true,
+        }),
+        FieldValue::Entries(entries) if entries.is_empty() => json!({
+            "type": "TextBlock",
+            "text": "<None>",
+        }),
+        FieldValue::Entries(entries) => json!({
+            "type": "FactSet",
+            "facts": entries
+                .iter()
+                .map(|(key, val)| json!({ "title": key, "value": val }))
+                .collect::<Vec<_>>(),
+        }),
+    };
+
+    vec![heading, value]
+}
@@ -0,0 +1,234 @@
+use std::str::FromStr;
+
+use async_trait::async_trait;
+use clap::ValueEnum;
+use futures::StreamExt;
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderName, HeaderValue, CONTENT_TYPE};
+use sha2::Sha256;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::{errors::BroadcastStreamRecvError, BroadcastStream};
+
+use super::cloudevents::{self, CloudEvent};
+use super::content::{ContentBuilder, NotificationRecord};
+use super::{impl_loggable, impl_resource_update_stream, Loggable, Notifier, NotifierLogLevel};
+
+use crate::state::ResourceUpdate;
+
+/// Header carrying the HMAC-SHA256 signature of the request body, in the form
+/// `sha256=<hex digest>`
+const SIGNATURE_HEADER: &str = "x-k8s-notifier-signature";
+
+/// A custom header sent with every request, parsed from `<name>: <value>`
+#[derive(Debug, Clone)]
+pub struct WebhookHeader {
+    pub name: HeaderName,
+    pub value: HeaderValue,
+}
+
+impl FromStr for WebhookHeader {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = s.split_once(':').ok_or(format!(
+            "expected a header of the form <name>: <value>, got '{s}'"
+        ))?;
+
+        Ok(Self {
+            name: HeaderName::from_str(name.trim())
+                .map_err(|e| format!("invalid header name '{name}': {e}"))?,
+            value: HeaderValue::from_str(value.trim())
+                .map_err(|e| format!("invalid header value for '{name}': {e}"))?,
+        })
+    }
+}
+
+/// How requests to the webhook are authenticated
+#[derive(Debug, Clone)]
+pub enum WebhookAuth {
+    Bearer(String),
+    Basic {
+        username: String,
+        password: Option<String>,
+    },
+}
+
+/// How notifications are encoded in request bodies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum WebhookFormat {
+    /// A JSON [`NotificationRecord`]
+    Json,
+    /// A CloudEvent whose attributes and data are both encoded in the JSON body
+    #[value(name = "cloudevents-structured")]
+    CloudEventsStructured,
+    /// A CloudEvent whose attributes are sent as `ce-` headers, with the JSON
+    /// [`NotificationRecord`] as the body
+    #[value(name = "cloudevents-binary")]
+    CloudEventsBinary,
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    /// The URL notifications are POSTed to
+    pub url: reqwest::Url,
+    pub format: WebhookFormat,
+    /// Headers sent with every request
+    pub headers: Vec<WebhookHeader>,
+    pub auth: Option<WebhookAuth>,
+    /// Secret used to sign request bodies, so that receivers can verify notifications
+    /// came from this notifier
+    pub signing_secret: Option<String>,
+}
+
+/// Notifier which POSTs a JSON [`NotificationRecord`], optionally wrapped in a
+/// CloudEvent, to a URL for each notification
+pub struct WebhookNotifier {
+    rx: BroadcastStream<ResourceUpdate>,
+    config: WebhookConfig,
+    client: reqwest::Client,
+    log_level: NotifierLogLevel,
+    content_builder: ContentBuilder,
+}
+
+impl WebhookNotifier {
+    pub fn new(
+        rx: broadcast::Receiver<ResourceUpdate>,
+        config: WebhookConfig,
+        log_level: NotifierLogLevel,
+        cluster_name: String,
+    ) -> Self {
+        Self {
+            rx: BroadcastStream::new(rx),
+            config,
+            client: reqwest::Client::new(),
+            log_level,
+            content_builder: ContentBuilder::new(cluster_name),
+        }
+    }
+
+    /// Signs `body` with the configured secret, returning the signature header value
+    fn sign(&self, body: &[u8]) -> Option<String> {
+        let secret = self.config.signing_secret.as_ref()?;
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(body);
+
+        Some(format!(
+            "sha256={}",
+            hex::encode(mac.finalize().into_bytes())
+        ))
+    }
+
+    /// Encodes `record` in the configured format
+    fn encode(
+        &self,
+        update: &ResourceUpdate,
+        record: NotificationRecord,
+    ) -> serde_json::Result<WebhookNotification> {
+        let level = record.level;
+
+        match self.config.format {
+            WebhookFormat::Json => Ok(WebhookNotification {
+                level,
+                content_type: "application/json",
+                headers: vec![],
+                body: serde_json::to_vec(&record)?,
+            }),
+            WebhookFormat::CloudEventsStructured => Ok(WebhookNotification {
+                level,
+                content_type: cloudevents::STRUCTURED_CONTENT_TYPE,
+                headers: vec![],
+                body: serde_json::to_vec(&CloudEvent::new(update, record))?,
+            }),
+            WebhookFormat::CloudEventsBinary => {
+                let event = CloudEvent::new(update, record);
+
+                Ok(WebhookNotification {
+                    level,
+                    content_type: event.datacontenttype,
+                    headers: event.binary_headers(),
+                    body: serde_json::to_vec(&event.data)?,
+                })
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    type Notification = WebhookNotification;
+
+    fn create_notifications(&self, update: &ResourceUpdate) -> Vec<Self::Notification> {
+        self.content_builder
+            .build(update)
+            .into_iter()
+            .filter_map(|content| {
+                let record =
+                    NotificationRecord::new(self.content_builder.cluster_name(), update, &content);
+
+                match self.encode(update, record) {
+                    Ok(notification) => Some(notification),
+                    Err(e) => {
+                        tracing::error!("Failed to serialize webhook notification: {e}");
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn log_level(&self) -> NotifierLogLevel {
+        self.log_level
+    }
+
+    async fn emit_notification(&self, notification: Self::Notification) -> anyhow::Result<()> {
+        let mut request = self
+            .client
+            .post(self.config.url.clone())
+            .header(CONTENT_TYPE, notification.content_type);
+
+        for (name, value) in notification.headers {
+            request = request.header(name, value);
+        }
+
+        for header in &self.config.headers {
+            request = request.header(header.name.clone(), header.value.clone());
+        }
+
+        request = match &self.config.auth {
+            Some(WebhookAuth::Bearer(token)) => request.bearer_auth(token),
+            Some(WebhookAuth::Basic { username, password }) => {
+                request.basic_auth(username, password.as_ref())
+            }
+            None => request,
+        };
+
+        if let Some(signature) = self.sign(&notification.body) {
+            request = request.header(SIGNATURE_HEADER, signature);
+        }
+
+        let res = request.body(notification.body).send().await?;
+
+        tracing::info!(
+            "Received status {} upon emitting webhook notification",
+            res.status()
+        );
+
+        res.error_for_status()?;
+
+        Ok(())
+    }
+}
+
+impl_resource_update_stream!(WebhookNotifier, rx);
+
+pub struct WebhookNotification {
+    level: NotifierLogLevel,
+    content_type: &'static str,
+    /// Headers specific to this notification, e.g. CloudEvents attributes
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl_loggable!(WebhookNotification, level);
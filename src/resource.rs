@@ -1,11 +1,18 @@
-use clap::ValueEnum;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use k8s_openapi::api::apps::v1::{DaemonSet, Deployment, StatefulSet};
+use k8s_openapi::api::batch::v1::{CronJob, Job};
 use k8s_openapi::api::core::v1::{Event, Node, Pod};
+use kube::api::{ApiResource, DynamicObject, ResourceExt};
+use kube::core::GroupVersionKind;
 
 pub mod ext;
 
 /// Packs API resources into a single type in order to create a unified resource
 /// stream containing any registered resources for watching
 #[derive(Debug, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum PackedResource {
     /// A Node resource
     Node(Node),
@@ -13,14 +20,151 @@ pub enum PackedResource {
     Pod(Pod),
     /// A Event resource
     Event(Event),
+    /// A Deployment resource
+    Deployment(Deployment),
+    /// A StatefulSet resource
+    StatefulSet(StatefulSet),
+    /// A DaemonSet resource
+    DaemonSet(DaemonSet),
+    /// A Job resource
+    Job(Job),
+    /// A CronJob resource
+    CronJob(CronJob),
+    /// An arbitrary resource (e.g. a custom resource) resolved through API discovery,
+    /// along with the API resource it was discovered as. Objects received from watches
+    /// don't carry their type metadata, so this is where their kind is taken from
+    Dynamic(DynamicObject, ApiResource),
+}
+
+/// Evaluates `$body` with `$inner` bound to the resource contained in a [`PackedResource`]
+macro_rules! with_packed_resource {
+    ($resource:expr, $inner:ident => $body:expr) => {
+        match $resource {
+            PackedResource::Node($inner) => $body,
+            PackedResource::Pod($inner) => $body,
+            PackedResource::Event($inner) => $body,
+            PackedResource::Deployment($inner) => $body,
+            PackedResource::StatefulSet($inner) => $body,
+            PackedResource::DaemonSet($inner) => $body,
+            PackedResource::Job($inner) => $body,
+            PackedResource::CronJob($inner) => $body,
+            PackedResource::Dynamic($inner, _) => $body,
+        }
+    };
+}
+
+impl PackedResource {
+    /// The kind of the packed resource, e.g. `Pod`
+    pub fn kind(&self) -> String {
+        match self {
+            PackedResource::Node(_) => "Node".to_string(),
+            PackedResource::Pod(_) => "Pod".to_string(),
+            PackedResource::Event(_) => "Event".to_string(),
+            PackedResource::Deployment(_) => "Deployment".to_string(),
+            PackedResource::StatefulSet(_) => "StatefulSet".to_string(),
+            PackedResource::DaemonSet(_) => "DaemonSet".to_string(),
+            PackedResource::Job(_) => "Job".to_string(),
+            PackedResource::CronJob(_) => "CronJob".to_string(),
+            PackedResource::Dynamic(_, api_resource) => api_resource.kind.clone(),
+        }
+    }
+
+    /// The UID of the packed resource, if it has one
+    pub fn uid(&self) -> Option<String> {
+        with_packed_resource!(self, inner => inner.uid())
+    }
+
+    /// Wrapper around [`ResourceExt::name_any`]
+    pub fn name(&self) -> String {
+        with_packed_resource!(self, inner => inner.name_any())
+    }
+
+    /// Wrapper around [`ResourceExt::namespace`]
+    pub fn namespace(&self) -> Option<String> {
+        with_packed_resource!(self, inner => inner.namespace())
+    }
+
+    /// The resource in the form returned by the API server
+    pub fn to_json(&self) -> serde_json::Value {
+        with_packed_resource!(self, inner => serde_json::to_value(inner).unwrap_or_default())
+    }
+
+    /// Wrapper around [`ResourceExt::labels`]
+    pub fn labels(&self) -> &BTreeMap<String, String> {
+        with_packed_resource!(self, inner => inner.labels())
+    }
+}
+
+/// The kind of change observed for a resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChangeType {
+    /// The resource was created or modified
+    Applied,
+    /// The resource was deleted
+    Deleted,
+    /// The resource was listed after its watch was (re)started
+    Restarted,
+}
+
+impl std::fmt::Display for ChangeType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChangeType::Applied => write!(f, "applied"),
+            ChangeType::Deleted => write!(f, "deleted"),
+            ChangeType::Restarted => write!(f, "restarted"),
+        }
+    }
 }
 
 /// A watched resource
-#[derive(Debug, Clone, Copy, ValueEnum)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum WatchedResource {
     Node,
     Pod,
     Event,
+    Deployment,
+    StatefulSet,
+    DaemonSet,
+    Job,
+    CronJob,
+    /// An arbitrary resource identified by its group, version and kind
+    Dynamic(GroupVersionKind),
+}
+
+impl FromStr for WatchedResource {
+    type Err = String;
+
+    /// Parses one of the built-in resources (e.g. `pod`) or an arbitrary resource in
+    /// the form `<group>/<version>/<kind>`, e.g. `cert-manager.io/v1/Certificate`.
+    /// Resources in the core group may be given as `<version>/<kind>`, e.g. `v1/ConfigMap`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let resource = match s.to_lowercase().as_str() {
+            "node" => WatchedResource::Node,
+            "pod" => WatchedResource::Pod,
+            "event" => WatchedResource::Event,
+            "deployment" => WatchedResource::Deployment,
+            "statefulset" => WatchedResource::StatefulSet,
+            "daemonset" => WatchedResource::DaemonSet,
+            "job" => WatchedResource::Job,
+            "cronjob" => WatchedResource::CronJob,
+            _ => match s.split('/').collect::<Vec<_>>()[..] {
+                [group, version, kind] if !version.is_empty() && !kind.is_empty() => {
+                    WatchedResource::Dynamic(GroupVersionKind::gvk(group, version, kind))
+                }
+                [version, kind] if !version.is_empty() && !kind.is_empty() => {
+                    WatchedResource::Dynamic(GroupVersionKind::gvk("", version, kind))
+                }
+                _ => {
+                    return Err(format!(
+                        "invalid resource '{s}'. Expected one of node, pod, event, deployment, \
+                         statefulset, daemonset, job, cronjob or <group>/<version>/<kind>"
+                    ))
+                }
+            },
+        };
+
+        Ok(resource)
+    }
 }
 
 impl std::fmt::Display for WatchedResource {
@@ -29,6 +173,17 @@ impl std::fmt::Display for WatchedResource {
             WatchedResource::Node => "node",
             WatchedResource::Pod => "pod",
             WatchedResource::Event => "event",
+            WatchedResource::Deployment => "deployment",
+            WatchedResource::StatefulSet => "statefulset",
+            WatchedResource::DaemonSet => "daemonset",
+            WatchedResource::Job => "job",
+            WatchedResource::CronJob => "cronjob",
+            WatchedResource::Dynamic(gvk) if gvk.group.is_empty() => {
+                return write!(f, "{}/{}", gvk.version, gvk.kind)
+            }
+            WatchedResource::Dynamic(gvk) => {
+                return write!(f, "{}/{}/{}", gvk.group, gvk.version, gvk.kind)
+            }
         };
 
         write!(f, "{}", typ)
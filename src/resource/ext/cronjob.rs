@@ -0,0 +1,315 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+use k8s_openapi::api::batch::v1::CronJob;
+use kube::api::ResourceExt;
+
+/// Helper methods for [`CronJob`]
+pub trait CronJobExt {
+    /// Wrapper around [`ResourceExt::namespace`]
+    fn namespace(&self) -> Option<String>;
+    /// Wrapper around [`ResourceExt::name_any`]
+    fn name(&self) -> String;
+    /// Wrapper around [`ResourceExt::labels`]
+    fn labels(&self) -> &BTreeMap<String, String>;
+    /// The schedule in Cron format, e.g. `*/5 * * * *`
+    fn schedule(&self) -> Option<&String>;
+    /// The time zone the schedule is evaluated in, e.g. `Europe/London`. The
+    /// controller's local time zone (typically UTC) is used when unset
+    fn time_zone(&self) -> Option<&String>;
+    /// Whether subsequent executions of this cron job are suspended
+    fn suspended(&self) -> bool;
+    /// The number of jobs which are currently running
+    fn active_jobs(&self) -> usize;
+    /// The last time a job was successfully scheduled
+    fn last_schedule_time(&self) -> Option<&DateTime<Utc>>;
+    /// The last time a job successfully completed
+    fn last_successful_time(&self) -> Option<&DateTime<Utc>>;
+    /// The time at which the next job should be scheduled, based on the last time
+    /// a job was scheduled (or the creation time if one never has been). Schedules
+    /// are evaluated in the cron job's time zone, or UTC if it doesn't have one.
+    /// [`None`] if the schedule or time zone can't be parsed
+    fn next_schedule_time(&self) -> Option<DateTime<Utc>>;
+    /// Returns the time at which a job should have been scheduled if it is more
+    /// than `grace_period` behind `now`. Suspended cron jobs never miss a schedule
+    fn missed_schedule(&self, now: DateTime<Utc>, grace_period: Duration) -> Option<DateTime<Utc>>;
+}
+
+impl CronJobExt for CronJob {
+    fn namespace(&self) -> Option<String> {
+        ResourceExt::namespace(self)
+    }
+
+    fn name(&self) -> String {
+        self.name_any()
+    }
+
+    fn labels(&self) -> &BTreeMap<String, String> {
+        ResourceExt::labels(self)
+    }
+
+    fn schedule(&self) -> Option<&String> {
+        Some(&self.spec.as_ref()?.schedule)
+    }
+
+    fn time_zone(&self) -> Option<&String> {
+        self.spec.as_ref()?.time_zone.as_ref()
+    }
+
+    fn suspended(&self) -> bool {
+        self.spec
+            .as_ref()
+            .and_then(|spec| spec.suspend)
+            .unwrap_or(false)
+    }
+
+    fn active_jobs(&self) -> usize {
+        self.status
+            .as_ref()
+            .and_then(|status| status.active.as_ref())
+            .map(|active| active.len())
+            .unwrap_or(0)
+    }
+
+    fn last_schedule_time(&self) -> Option<&DateTime<Utc>> {
+        self.status
+            .as_ref()?
+            .last_schedule_time
+            .as_ref()
+            .map(|t| &t.0)
+    }
+
+    fn last_successful_time(&self) -> Option<&DateTime<Utc>> {
+        self.status
+            .as_ref()?
+            .last_successful_time
+            .as_ref()
+            .map(|t| &t.0)
+    }
+
+    fn next_schedule_time(&self) -> Option<DateTime<Utc>> {
+        let schedule = parse_schedule(self.schedule()?)?;
+        let since = self.last_schedule_time().or(self
+            .metadata
+            .creation_timestamp
+            .as_ref()
+            .map(|t| &t.0))?;
+
+        let time_zone = match self.time_zone() {
+            Some(time_zone) => time_zone.parse::<Tz>().ok()?,
+            None => Tz::UTC,
+        };
+
+        schedule
+            .after(&since.with_timezone(&time_zone))
+            .next()
+            .map(|next| next.with_timezone(&Utc))
+    }
+
+    fn missed_schedule(&self, now: DateTime<Utc>, grace_period: Duration) -> Option<DateTime<Utc>> {
+        if self.suspended() {
+            return None;
+        }
+
+        self.next_schedule_time()
+            .filter(|next| *next + grace_period < now)
+    }
+}
+
+/// Parses a standard 5 field cron expression, as used by Kubernetes, into a [`Schedule`].
+///
+/// [`Schedule`] expects a leading seconds field and numbers days of the week from 1
+/// (Sunday) to 7, whereas standard cron numbers them from 0 (Sunday) to 6, with 7
+/// also meaning Sunday
+fn parse_schedule(expression: &str) -> Option<Schedule> {
+    let expression = expression.trim();
+
+    if expression.starts_with('@') {
+        return Schedule::from_str(expression).ok();
+    }
+
+    let mut fields = expression.split_whitespace().collect::<Vec<_>>();
+    if fields.len() != 5 {
+        return None;
+    }
+
+    let day_of_week = shift_day_of_week(fields[4]);
+    fields[4] = &day_of_week;
+
+    Schedule::from_str(&format!("0 {}", fields.join(" "))).ok()
+}
+
+/// Shifts the numeric values of a day-of-week field from `0-7` to `1-7`. Numeric
+/// ranges and steps are expanded into lists of days, since Sunday may appear at
+/// either end of the week (e.g. `1-7/2` includes Sunday). Names such as `MON-FRI`
+/// are left as they are
+fn shift_day_of_week(field: &str) -> String {
+    field
+        .split(',')
+        .map(|part| match expand_days(part) {
+            Some(days) => days
+                .into_iter()
+                .map(|day| (day % 7 + 1).to_string())
+                .collect::<Vec<_>>()
+                .join(","),
+            None => part.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Lists the days (from `0` to `7`) matched by a numeric day-of-week value, range or
+/// step, e.g. `1-5/2`. [`None`] for `*`, names or anything invalid
+fn expand_days(part: &str) -> Option<Vec<u8>> {
+    let (range, step) = match part.split_once('/') {
+        Some((range, step)) => (range, Some(step.parse::<usize>().ok()?)),
+        None => (part, None),
+    };
+
+    let (start, end) = match (range, range.split_once('-')) {
+        ("*", _) if step.is_some() => (0, 6),
+        (_, Some((start, end))) => (start.parse().ok()?, end.parse().ok()?),
+        // A single day with a step continues until the end of the week
+        (day, None) if step.is_some() => (day.parse().ok()?, 6),
+        (day, None) => {
+            let day = day.parse().ok()?;
+            (day, day)
+        }
+    };
+
+    if start > end || end > 7 || step == Some(0) {
+        return None;
+    }
+
+    Some((start..=end).step_by(step.unwrap_or(1)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+    use k8s_openapi::api::batch::v1::{CronJobSpec, CronJobStatus};
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::Time;
+
+    use super::*;
+
+    /// The next `count` times `expression` is scheduled at after 2024-01-01 00:00 UTC,
+    /// which is a Monday
+    fn upcoming(expression: &str, count: usize) -> Vec<String> {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        parse_schedule(expression)
+            .unwrap_or_else(|| panic!("'{expression}' should parse"))
+            .after(&start)
+            .take(count)
+            .map(|t| t.format("%a %d %H:%M").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn shifts_days_of_week() {
+        for (field, shifted) in [
+            ("*", "*"),
+            ("0", "1"),
+            ("7", "1"),
+            ("1,3", "2,4"),
+            ("1-5", "2,3,4,5,6"),
+            ("5-7", "6,7,1"),
+            ("1-7/2", "2,4,6,1"),
+            ("*/3", "1,4,7"),
+            ("2/2", "3,5,7"),
+            ("MON-FRI", "MON-FRI"),
+            ("SUN,6", "SUN,7"),
+        ] {
+            assert_eq!(shift_day_of_week(field), shifted, "{field}");
+        }
+    }
+
+    #[test]
+    fn parses_standard_expressions() {
+        for (expression, expected) in [
+            ("*/15 * * * *", vec!["Mon 01 00:15", "Mon 01 00:30"]),
+            ("30 2 * * *", vec!["Mon 01 02:30", "Tue 02 02:30"]),
+            ("0 9 * * 1-5", vec!["Mon 01 09:00", "Tue 02 09:00"]),
+            ("0 9 * * 0", vec!["Sun 07 09:00", "Sun 14 09:00"]),
+            ("0 9 * * 7", vec!["Sun 07 09:00", "Sun 14 09:00"]),
+            (
+                "0 9 * * 1-7/2",
+                vec![
+                    "Mon 01 09:00",
+                    "Wed 03 09:00",
+                    "Fri 05 09:00",
+                    "Sun 07 09:00",
+                ],
+            ),
+            ("0 9 * * SAT,SUN", vec!["Sat 06 09:00", "Sun 07 09:00"]),
+            ("0 0 1 JAN *", vec!["Wed 01 00:00"]),
+            ("@daily", vec!["Tue 02 00:00", "Wed 03 00:00"]),
+            ("@hourly", vec!["Mon 01 01:00", "Mon 01 02:00"]),
+            ("  0 12 * * *  ", vec!["Mon 01 12:00"]),
+        ] {
+            assert_eq!(
+                upcoming(expression, expected.len()),
+                expected,
+                "{expression}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_expressions() {
+        for expression in ["", "* * * *", "0 0 * * * *", "61 * * * *", "0 0 * * 8"] {
+            assert!(parse_schedule(expression).is_none(), "{expression}");
+        }
+    }
+
+    fn cron_job(schedule: &str, time_zone: Option<&str>, last_scheduled: DateTime<Utc>) -> CronJob {
+        CronJob {
+            spec: Some(CronJobSpec {
+                schedule: schedule.to_string(),
+                time_zone: time_zone.map(str::to_string),
+                ..Default::default()
+            }),
+            status: Some(CronJobStatus {
+                last_schedule_time: Some(Time(last_scheduled)),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn evaluates_schedules_in_their_time_zone() {
+        let last_scheduled = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+
+        let utc = cron_job("0 9 * * *", None, last_scheduled);
+        assert_eq!(
+            utc.next_schedule_time(),
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 9, 0, 0).unwrap())
+        );
+
+        let new_york = cron_job("0 9 * * *", Some("America/New_York"), last_scheduled);
+        assert_eq!(
+            new_york.next_schedule_time(),
+            Some(Utc.with_ymd_and_hms(2024, 1, 1, 14, 0, 0).unwrap())
+        );
+
+        let invalid = cron_job("0 9 * * *", Some("Mars/Olympus_Mons"), last_scheduled);
+        assert_eq!(invalid.next_schedule_time(), None);
+    }
+
+    #[test]
+    fn misses_schedule_after_grace_period() {
+        let last_scheduled = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let cron_job = cron_job("0 9 * * *", Some("Europe/Paris"), last_scheduled);
+        let due = Utc.with_ymd_and_hms(2024, 1, 1, 8, 0, 0).unwrap();
+
+        assert_eq!(cron_job.missed_schedule(due, Duration::minutes(5)), None);
+        assert_eq!(
+            cron_job.missed_schedule(due + Duration::minutes(6), Duration::minutes(5)),
+            Some(due)
+        );
+    }
+}
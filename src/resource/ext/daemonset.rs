@@ -0,0 +1,71 @@
+use std::collections::BTreeMap;
+
+use k8s_openapi::api::apps::v1::DaemonSet;
+use kube::api::ResourceExt;
+
+use super::rollout::{stalled_reason, RolloutStatus};
+
+/// Helper methods for [`DaemonSet`]
+pub trait DaemonSetExt {
+    /// Wrapper around [`ResourceExt::namespace`]
+    fn namespace(&self) -> Option<String>;
+    /// Wrapper around [`ResourceExt::name_any`]
+    fn name(&self) -> String;
+    /// Wrapper around [`ResourceExt::labels`]
+    fn labels(&self) -> &BTreeMap<String, String>;
+    /// Returns a map of the daemon set's conditions in the form:
+    ///
+    /// ```json
+    /// {
+    ///     "Available": "True"
+    /// }
+    fn status_conditions(&self) -> Option<BTreeMap<&String, &String>>;
+    /// The current state of the daemon set's rollout
+    fn rollout_status(&self) -> RolloutStatus;
+}
+
+impl DaemonSetExt for DaemonSet {
+    fn namespace(&self) -> Option<String> {
+        ResourceExt::namespace(self)
+    }
+
+    fn name(&self) -> String {
+        self.name_any()
+    }
+
+    fn labels(&self) -> &BTreeMap<String, String> {
+        ResourceExt::labels(self)
+    }
+
+    fn status_conditions(&self) -> Option<BTreeMap<&String, &String>> {
+        Some(
+            self.status
+                .as_ref()?
+                .conditions
+                .as_ref()?
+                .iter()
+                .map(|condition| (&condition.type_, &condition.status))
+                .collect(),
+        )
+    }
+
+    fn rollout_status(&self) -> RolloutStatus {
+        let Some(status) = self.status.as_ref() else {
+            return RolloutStatus::default();
+        };
+
+        RolloutStatus {
+            desired: status.desired_number_scheduled,
+            updated: status.updated_number_scheduled.unwrap_or(0),
+            ready: status.number_ready,
+            available: status.number_available.unwrap_or(0),
+            stalled_reason: stalled_reason(
+                status
+                    .conditions
+                    .iter()
+                    .flatten()
+                    .map(|c| (c.type_.as_str(), c.status.as_str(), c.reason.as_ref())),
+            ),
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use std::collections::BTreeMap;
+
+use k8s_openapi::api::apps::v1::Deployment;
+use kube::api::ResourceExt;
+
+use super::rollout::{stalled_reason, RolloutStatus};
+
+/// Helper methods for [`Deployment`]
+pub trait DeploymentExt {
+    /// Wrapper around [`ResourceExt::namespace`]
+    fn namespace(&self) -> Option<String>;
+    /// Wrapper around [`ResourceExt::name_any`]
+    fn name(&self) -> String;
+    /// Wrapper around [`ResourceExt::labels`]
+    fn labels(&self) -> &BTreeMap<String, String>;
+    /// Returns a map of the deployment's conditions in the form:
+    ///
+    /// ```json
+    /// {
+    ///     "Available": "True",
+    ///     "Progressing": "True"
+    /// }
+    fn status_conditions(&self) -> Option<BTreeMap<&String, &String>>;
+    /// The current state of the deployment's rollout
+    fn rollout_status(&self) -> RolloutStatus;
+}
+
+impl DeploymentExt for Deployment {
+    fn namespace(&self) -> Option<String> {
+        ResourceExt::namespace(self)
+    }
+
+    fn name(&self) -> String {
+        self.name_any()
+    }
+
+    fn labels(&self) -> &BTreeMap<String, String> {
+        ResourceExt::labels(self)
+    }
+
+    fn status_conditions(&self) -> Option<BTreeMap<&String, &String>> {
+        Some(
+            self.status
+                .as_ref()?
+                .conditions
+                .as_ref()?
+                .iter()
+                .map(|condition| (&condition.type_, &condition.status))
+                .collect(),
+        )
+    }
+
+    fn rollout_status(&self) -> RolloutStatus {
+        let desired = self
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.replicas)
+            .unwrap_or(1);
+
+        let Some(status) = self.status.as_ref() else {
+            return RolloutStatus {
+                desired,
+                ..Default::default()
+            };
+        };
+
+        RolloutStatus {
+            desired,
+            updated: status.updated_replicas.unwrap_or(0),
+            ready: status.ready_replicas.unwrap_or(0),
+            available: status.available_replicas.unwrap_or(0),
+            stalled_reason: stalled_reason(
+                status
+                    .conditions
+                    .iter()
+                    .flatten()
+                    .map(|c| (c.type_.as_str(), c.status.as_str(), c.reason.as_ref())),
+            ),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::BTreeMap;
+
+use kube::api::{DynamicObject, ResourceExt};
+
+/// A condition read from an arbitrary resource's `status.conditions`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DynamicCondition {
+    /// Type of the condition, e.g. `Ready`
+    pub type_: String,
+    /// Status of the condition, one of `True`, `False` or `Unknown`
+    pub status: String,
+    /// Machine readable reason for the condition's last transition
+    pub reason: Option<String>,
+    /// Human readable message indicating details about the last transition
+    pub message: Option<String>,
+}
+
+impl DynamicCondition {
+    /// Whether this condition indicates the resource is unhealthy. This is the case
+    /// when a positive condition (e.g. `Ready`) is `False`, or a negative condition
+    /// (e.g. `Degraded` or `ReplicaFailure`) is `True`
+    pub fn is_failing(&self) -> bool {
+        if self.is_negative() {
+            self.status == "True"
+        } else {
+            self.status == "False"
+        }
+    }
+
+    /// Whether the state of this condition is unknown
+    pub fn is_unknown(&self) -> bool {
+        self.status == "Unknown"
+    }
+
+    fn is_negative(&self) -> bool {
+        const NEGATIVE_TYPES: &[&str] = &["Degraded", "Stalled", "InvalidSpec"];
+
+        NEGATIVE_TYPES.contains(&self.type_.as_str())
+            || self.type_.ends_with("Failed")
+            || self.type_.ends_with("Failure")
+            || self.type_.ends_with("Pressure")
+    }
+}
+
+/// Helper methods for [`DynamicObject`]
+pub trait DynamicObjectExt {
+    /// Wrapper around [`ResourceExt::namespace`]
+    fn namespace(&self) -> Option<String>;
+    /// Wrapper around [`ResourceExt::name_any`]
+    fn name(&self) -> String;
+    /// Wrapper around [`ResourceExt::labels`]
+    fn labels(&self) -> &BTreeMap<String, String>;
+    /// Conditions read from the object's `status.conditions`, if it follows the
+    /// standard Kubernetes condition conventions
+    fn status_conditions(&self) -> Vec<DynamicCondition>;
+}
+
+impl DynamicObjectExt for DynamicObject {
+    fn namespace(&self) -> Option<String> {
+        ResourceExt::namespace(self)
+    }
+
+    fn name(&self) -> String {
+        self.name_any()
+    }
+
+    fn labels(&self) -> &BTreeMap<String, String> {
+        ResourceExt::labels(self)
+    }
+
+    fn status_conditions(&self) -> Vec<DynamicCondition> {
+        let Some(conditions) = self.data["status"]["conditions"].as_array() else {
+            return vec![];
+        };
+
+        conditions
+            .iter()
+            .filter_map(|condition| {
+                let field = |name: &str| condition[name].as_str().map(str::to_string);
+
+                Some(DynamicCondition {
+                    type_: field("type")?,
+                    status: field("status")?,
+                    reason: field("reason"),
+                    message: field("message"),
+                })
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::BTreeMap;
+
+use k8s_openapi::api::batch::v1::Job;
+use kube::api::ResourceExt;
+
+/// Helper methods for [`Job`]
+pub trait JobExt {
+    /// Wrapper around [`ResourceExt::namespace`]
+    fn namespace(&self) -> Option<String>;
+    /// Wrapper around [`ResourceExt::name_any`]
+    fn name(&self) -> String;
+    /// Wrapper around [`ResourceExt::labels`]
+    fn labels(&self) -> &BTreeMap<String, String>;
+    /// Returns a map of the job's conditions in the form:
+    ///
+    /// ```json
+    /// {
+    ///     "Complete": "True"
+    /// }
+    fn status_conditions(&self) -> Option<BTreeMap<&String, &String>>;
+    /// Whether the job has completed successfully
+    fn complete(&self) -> bool;
+    /// The reason the job failed (e.g. `BackoffLimitExceeded` or `DeadlineExceeded`),
+    /// if it has failed
+    fn failure_reason(&self) -> Option<&String>;
+    /// A human readable message describing why the job failed, if it has failed
+    fn failure_message(&self) -> Option<&String>;
+    /// The number of pods which are currently running
+    fn active_pods(&self) -> i32;
+    /// The number of pods which reached phase `Succeeded`
+    fn succeeded_pods(&self) -> i32;
+    /// The number of pods which reached phase `Failed`
+    fn failed_pods(&self) -> i32;
+    /// The number of retries before the job is marked as failed
+    fn backoff_limit(&self) -> i32;
+}
+
+impl JobExt for Job {
+    fn namespace(&self) -> Option<String> {
+        ResourceExt::namespace(self)
+    }
+
+    fn name(&self) -> String {
+        self.name_any()
+    }
+
+    fn labels(&self) -> &BTreeMap<String, String> {
+        ResourceExt::labels(self)
+    }
+
+    fn status_conditions(&self) -> Option<BTreeMap<&String, &String>> {
+        Some(
+            self.status
+                .as_ref()?
+                .conditions
+                .as_ref()?
+                .iter()
+                .map(|condition| (&condition.type_, &condition.status))
+                .collect(),
+        )
+    }
+
+    fn complete(&self) -> bool {
+        self.status
+            .as_ref()
+            .and_then(|status| status.conditions.as_ref())
+            .map(|conditions| {
+                conditions
+                    .iter()
+                    .any(|c| c.type_ == "Complete" && c.status == "True")
+            })
+            .unwrap_or(false)
+    }
+
+    fn failure_reason(&self) -> Option<&String> {
+        let condition = self
+            .status
+            .as_ref()?
+            .conditions
+            .as_ref()?
+            .iter()
+            .find(|c| c.type_ == "Failed" && c.status == "True")?;
+
+        Some(condition.reason.as_ref().unwrap_or(&condition.type_))
+    }
+
+    fn failure_message(&self) -> Option<&String> {
+        self.status
+            .as_ref()?
+            .conditions
+            .as_ref()?
+            .iter()
+            .find(|c| c.type_ == "Failed" && c.status == "True")?
+            .message
+            .as_ref()
+    }
+
+    fn active_pods(&self) -> i32 {
+        self.status
+            .as_ref()
+            .and_then(|status| status.active)
+            .unwrap_or(0)
+    }
+
+    fn succeeded_pods(&self) -> i32 {
+        self.status
+            .as_ref()
+            .and_then(|status| status.succeeded)
+            .unwrap_or(0)
+    }
+
+    fn failed_pods(&self) -> i32 {
+        self.status
+            .as_ref()
+            .and_then(|status| status.failed)
+            .unwrap_or(0)
+    }
+
+    fn backoff_limit(&self) -> i32 {
+        // Kubernetes defaults the backoff limit to 6
+        self.spec
+            .as_ref()
+            .and_then(|spec| spec.backoff_limit)
+            .unwrap_or(6)
+    }
+}
@@ -1,3 +1,10 @@
+pub mod cronjob;
+pub mod daemonset;
+pub mod deployment;
+pub mod dynamic;
 pub mod event;
+pub mod job;
 pub mod node;
 pub mod pod;
+pub mod rollout;
+pub mod statefulset;
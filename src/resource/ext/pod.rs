@@ -1,20 +1,123 @@
 use std::collections::BTreeMap;
 
-use k8s_openapi::api::core::v1::Pod;
+use k8s_openapi::api::core::v1::{ContainerStatus, Pod};
 use kube::api::ResourceExt;
 
+/// Waiting reasons which indicate a container is failing to start or keeps crashing
+const FAILING_WAITING_REASONS: &[&str] = &[
+    "CrashLoopBackOff",
+    "ImagePullBackOff",
+    "ErrImagePull",
+    "InvalidImageName",
+    "CreateContainerConfigError",
+    "CreateContainerError",
+    "RunContainerError",
+];
+
+/// The status of a single container within a pod
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PodContainerStatus {
+    /// Name of the container
+    pub name: String,
+    /// Whether this is an init container
+    pub init: bool,
+    /// The number of times the container has been restarted
+    pub restart_count: i32,
+    /// Whether the container is currently running
+    pub running: bool,
+    /// Why the container is waiting to run, e.g. `CrashLoopBackOff`
+    pub waiting_reason: Option<String>,
+    /// Why the container terminated, if it is currently terminated
+    pub terminated_reason: Option<String>,
+    /// Why the container terminated the last time it ran, e.g. `OOMKilled`
+    pub last_termination_reason: Option<String>,
+    /// Exit code of the container's current termination, or of its last termination if
+    /// it isn't currently terminated
+    pub exit_code: Option<i32>,
+}
+
+impl PodContainerStatus {
+    fn new(status: &ContainerStatus, init: bool) -> Self {
+        let state = status.state.as_ref();
+        let waiting = state.and_then(|s| s.waiting.as_ref());
+        let terminated = state.and_then(|s| s.terminated.as_ref());
+        let last_terminated = status
+            .last_state
+            .as_ref()
+            .and_then(|s| s.terminated.as_ref());
+
+        Self {
+            name: status.name.clone(),
+            init,
+            restart_count: status.restart_count,
+            running: state.is_some_and(|s| s.running.is_some()),
+            waiting_reason: waiting.and_then(|w| w.reason.clone()),
+            terminated_reason: terminated.and_then(|t| t.reason.clone()),
+            last_termination_reason: last_terminated.and_then(|t| t.reason.clone()),
+            exit_code: terminated.or(last_terminated).map(|t| t.exit_code),
+        }
+    }
+
+    /// The reason this container is failing, if it is. This is either a waiting reason
+    /// such as `CrashLoopBackOff` or `ImagePullBackOff`, or `OOMKilled` if the container
+    /// was killed for exceeding its memory limit and hasn't been running since
+    pub fn failure_reason(&self) -> Option<&String> {
+        if let Some(reason) = &self.waiting_reason {
+            if FAILING_WAITING_REASONS.contains(&reason.as_str()) {
+                return Some(reason);
+            }
+        }
+
+        // A container which was OOM killed once but has since recovered keeps it as
+        // its last termination, so that only counts while the container isn't running
+        let last_termination_reason = if self.running {
+            &None
+        } else {
+            &self.last_termination_reason
+        };
+
+        [&self.terminated_reason, last_termination_reason]
+            .into_iter()
+            .flatten()
+            .find(|reason| *reason == "OOMKilled")
+    }
+}
+
 /// Helper methods for [`Pod`]
 pub trait PodExt {
     /// The IP address of the pod within the cluster
     fn ip_addr(&self) -> Option<&String>;
     /// The phase the pod is currently in
     fn phase(&self) -> Option<&String>;
+    /// A brief reason the pod is in its current state, e.g. `Evicted`
+    fn reason(&self) -> Option<&String>;
     /// Wrapper around [`ResourceExt::namespace`]
     fn namespace(&self) -> Option<String>;
     /// Wrapper around [`ResourceExt::name_any`]
     fn name(&self) -> String;
     /// Wrapper around [`ResourceExt::labels`]
     fn labels(&self) -> &BTreeMap<String, String>;
+    /// Returns a map of the pod's conditions in the form:
+    ///
+    /// ```json
+    /// {
+    ///     "Ready": "True",
+    ///     "PodScheduled": "True"
+    /// }
+    fn status_conditions(&self) -> Option<BTreeMap<&String, &String>>;
+    /// Statuses of the pod's regular containers
+    fn container_statuses(&self) -> Vec<PodContainerStatus>;
+    /// Statuses of the pod's init containers
+    fn init_container_statuses(&self) -> Vec<PodContainerStatus>;
+    /// Statuses of all init and regular containers which are currently failing, e.g.
+    /// crash looping, OOM killed or unable to pull their image
+    fn failing_containers(&self) -> Vec<PodContainerStatus> {
+        self.init_container_statuses()
+            .into_iter()
+            .chain(self.container_statuses())
+            .filter(|status| status.failure_reason().is_some())
+            .collect()
+    }
 }
 
 impl PodExt for Pod {
@@ -35,7 +138,105 @@ impl PodExt for Pod {
         self.status.as_ref()?.phase.as_ref()
     }
 
+    fn reason(&self) -> Option<&String> {
+        self.status.as_ref()?.reason.as_ref()
+    }
+
     fn namespace(&self) -> Option<String> {
         ResourceExt::namespace(self)
     }
+
+    fn status_conditions(&self) -> Option<BTreeMap<&String, &String>> {
+        Some(
+            self.status
+                .as_ref()?
+                .conditions
+                .as_ref()?
+                .iter()
+                .map(|condition| (&condition.type_, &condition.status))
+                .collect(),
+        )
+    }
+
+    fn container_statuses(&self) -> Vec<PodContainerStatus> {
+        self.status
+            .as_ref()
+            .and_then(|status| status.container_statuses.as_ref())
+            .map(|statuses| {
+                statuses
+                    .iter()
+                    .map(|status| PodContainerStatus::new(status, false))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn init_container_statuses(&self) -> Vec<PodContainerStatus> {
+        self.status
+            .as_ref()
+            .and_then(|status| status.init_container_statuses.as_ref())
+            .map(|statuses| {
+                statuses
+                    .iter()
+                    .map(|status| PodContainerStatus::new(status, true))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(running: bool, last_termination_reason: Option<&str>) -> PodContainerStatus {
+        PodContainerStatus {
+            name: "app".to_string(),
+            init: false,
+            restart_count: 1,
+            running,
+            waiting_reason: None,
+            terminated_reason: None,
+            last_termination_reason: last_termination_reason.map(str::to_string),
+            exit_code: Some(137),
+        }
+    }
+
+    #[test]
+    fn recovered_oom_killed_container_is_not_failing() {
+        assert_eq!(status(true, Some("OOMKilled")).failure_reason(), None);
+    }
+
+    #[test]
+    fn oom_killed_container_is_failing_until_it_runs_again() {
+        let mut waiting = status(false, Some("OOMKilled"));
+        waiting.waiting_reason = Some("ContainerCreating".to_string());
+        assert_eq!(
+            waiting.failure_reason().map(String::as_str),
+            Some("OOMKilled")
+        );
+
+        let mut terminated = status(false, None);
+        terminated.terminated_reason = Some("OOMKilled".to_string());
+        assert_eq!(
+            terminated.failure_reason().map(String::as_str),
+            Some("OOMKilled")
+        );
+    }
+
+    #[test]
+    fn failing_waiting_reason_takes_precedence() {
+        let mut crash_looping = status(false, Some("OOMKilled"));
+        crash_looping.waiting_reason = Some("CrashLoopBackOff".to_string());
+        assert_eq!(
+            crash_looping.failure_reason().map(String::as_str),
+            Some("CrashLoopBackOff")
+        );
+    }
+
+    #[test]
+    fn healthy_container_is_not_failing() {
+        assert_eq!(status(true, Some("Error")).failure_reason(), None);
+        assert_eq!(status(true, None).failure_reason(), None);
+    }
 }
@@ -0,0 +1,43 @@
+/// A snapshot of a workload's rollout progress
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RolloutStatus {
+    /// The number of replicas the workload should be running
+    pub desired: i32,
+    /// The number of replicas running the latest revision
+    pub updated: i32,
+    /// The number of ready replicas
+    pub ready: i32,
+    /// The number of available replicas
+    pub available: i32,
+    /// The reason the rollout has stalled or failed to progress, if it has
+    pub stalled_reason: Option<String>,
+}
+
+impl RolloutStatus {
+    /// Whether every desired replica is updated, ready and available
+    pub fn is_complete(&self) -> bool {
+        self.updated >= self.desired && self.ready >= self.desired && self.available >= self.desired
+    }
+
+    /// Whether the rollout has stalled or failed to progress
+    pub fn is_stalled(&self) -> bool {
+        self.stalled_reason.is_some()
+    }
+}
+
+/// Finds the reason a rollout has stalled from a workload's status conditions,
+/// given as `(type, status, reason)` triples.
+///
+/// A rollout is considered stalled when its `Progressing` condition is `False`
+/// (e.g. `ProgressDeadlineExceeded`) or its `ReplicaFailure` condition is `True`
+pub(crate) fn stalled_reason<'a>(
+    conditions: impl IntoIterator<Item = (&'a str, &'a str, Option<&'a String>)>,
+) -> Option<String> {
+    conditions
+        .into_iter()
+        .find(|(typ, status, _)| {
+            (*typ == "Progressing" && *status == "False")
+                || (*typ == "ReplicaFailure" && *status == "True")
+        })
+        .map(|(typ, _, reason)| reason.cloned().unwrap_or_else(|| typ.to_string()))
+}
@@ -0,0 +1,407 @@
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use kube::runtime::watcher;
+
+use crate::resource::WatchedResource;
+
+/// A label or field selector, optionally scoped to a single kind of watched resource.
+///
+/// Parsed from either a bare selector (e.g. `team=payments`), which applies to all
+/// watched resources, or a selector prefixed with a resource and a colon (e.g.
+/// `pod:team=payments` or `event:type!=Normal`), which applies only to that resource
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScopedSelector {
+    /// The resource this selector applies to, or [`None`] if it applies to all resources
+    pub resource: Option<WatchedResource>,
+    /// The selector itself, e.g. `team=payments`
+    pub selector: String,
+}
+
+impl ScopedSelector {
+    fn parse(s: &str) -> Result<Self, String> {
+        let (resource, selector) = match s.split_once(':') {
+            Some((resource, selector)) => (Some(resource.parse()?), selector),
+            None => (None, s),
+        };
+
+        let selector = selector.trim();
+        if selector.is_empty() {
+            return Err(format!("empty selector '{s}'"));
+        }
+
+        Ok(Self {
+            resource,
+            selector: selector.to_string(),
+        })
+    }
+}
+
+/// A [`ScopedSelector`] which is validated as a label selector
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelSelector(pub ScopedSelector);
+
+impl FromStr for LabelSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let scoped = ScopedSelector::parse(s)?;
+        validate_label_selector(&scoped.selector)?;
+
+        Ok(Self(scoped))
+    }
+}
+
+/// A [`ScopedSelector`] which is validated as a field selector
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldSelector(pub ScopedSelector);
+
+impl FromStr for FieldSelector {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let scoped = ScopedSelector::parse(s)?;
+
+        for requirement in split_requirements(&scoped.selector)? {
+            validate_field_requirement(requirement)
+                .map_err(|e| format!("invalid field selector '{}': {e}", scoped.selector))?;
+        }
+
+        Ok(Self(scoped))
+    }
+}
+
+/// Label and field selectors restricting which objects are watched
+#[derive(Debug, Clone, Default)]
+pub struct ResourceSelectors {
+    label_selectors: Vec<LabelSelector>,
+    field_selectors: Vec<FieldSelector>,
+}
+
+impl ResourceSelectors {
+    pub fn new(label_selectors: Vec<LabelSelector>, field_selectors: Vec<FieldSelector>) -> Self {
+        Self {
+            label_selectors,
+            field_selectors,
+        }
+    }
+
+    /// Ensures every selector scoped to a resource refers to one of `resources`
+    pub fn validate(&self, resources: &[WatchedResource]) -> anyhow::Result<()> {
+        let scoped = self
+            .label_selectors
+            .iter()
+            .map(|s| &s.0)
+            .chain(self.field_selectors.iter().map(|s| &s.0));
+
+        for selector in scoped {
+            if let Some(resource) = &selector.resource {
+                if !resources.contains(resource) {
+                    anyhow::bail!(
+                        "Selector '{}' is scoped to '{resource}', which is not being watched",
+                        selector.selector
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Builds the watcher configuration for `resource`. Selectors which apply to all
+    /// resources are combined with those scoped to `resource`
+    pub fn watcher_config(&self, resource: &WatchedResource) -> watcher::Config {
+        let mut config = watcher::Config::default();
+
+        if let Some(labels) = combine(self.label_selectors.iter().map(|s| &s.0), resource) {
+            config = config.labels(&labels);
+        }
+
+        if let Some(fields) = combine(self.field_selectors.iter().map(|s| &s.0), resource) {
+            config = config.fields(&fields);
+        }
+
+        config
+    }
+}
+
+/// Joins all selectors applying to `resource` into a single selector, in which every
+/// requirement must be satisfied
+fn combine<'a>(
+    selectors: impl Iterator<Item = &'a ScopedSelector>,
+    resource: &WatchedResource,
+) -> Option<String> {
+    let combined = selectors
+        .filter(|s| s.resource.as_ref().is_none_or(|r| r == resource))
+        .map(|s| s.selector.as_str())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    (!combined.is_empty()).then_some(combined)
+}
+
+/// Validates an unscoped label selector, e.g. `team=payments,tier notin (test)`
+pub fn validate_label_selector(selector: &str) -> Result<(), String> {
+    for requirement in split_requirements(selector)? {
+        validate_label_requirement(requirement)
+            .map_err(|e| format!("invalid label selector '{selector}': {e}"))?;
+    }
+
+    Ok(())
+}
+
+/// Whether `labels` satisfy every requirement of a label selector, which must have been
+/// validated with [`validate_label_selector`]
+pub fn label_selector_matches(selector: &str, labels: &BTreeMap<String, String>) -> bool {
+    let Ok(requirements) = split_requirements(selector) else {
+        return false;
+    };
+
+    requirements
+        .into_iter()
+        .all(|requirement| label_requirement_matches(requirement, labels))
+}
+
+/// Splits a selector into its comma separated requirements, ignoring commas inside
+/// the parenthesized value sets of `in` and `notin` requirements
+fn split_requirements(selector: &str) -> Result<Vec<&str>, String> {
+    let mut requirements = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in selector.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Err(format!("unbalanced parentheses in '{selector}'")),
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                requirements.push(selector[start..i].trim());
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if depth != 0 {
+        return Err(format!("unbalanced parentheses in '{selector}'"));
+    }
+
+    requirements.push(selector[start..].trim());
+
+    Ok(requirements)
+}
+
+fn validate_label_requirement(requirement: &str) -> Result<(), String> {
+    if let Some(key) = requirement.strip_prefix('!') {
+        return validate_label_key(key.trim());
+    }
+
+    for op in ["==", "!=", "="] {
+        if let Some((key, value)) = requirement.split_once(op) {
+            validate_label_key(key.trim())?;
+            return validate_label_value(value.trim());
+        }
+    }
+
+    for op in [" notin ", " in "] {
+        if let Some((key, values)) = requirement.split_once(op) {
+            validate_label_key(key.trim())?;
+
+            let values = values
+                .trim()
+                .strip_prefix('(')
+                .and_then(|v| v.strip_suffix(')'))
+                .ok_or(format!(
+                    "expected a parenthesized set of values in '{requirement}'"
+                ))?;
+
+            return values
+                .split(',')
+                .try_for_each(|value| validate_label_value(value.trim()));
+        }
+    }
+
+    // A bare key requires that the label exists
+    validate_label_key(requirement)
+}
+
+/// Matches a single requirement as the API server would, in which `!=` and `notin`
+/// are satisfied by objects without the label
+fn label_requirement_matches(requirement: &str, labels: &BTreeMap<String, String>) -> bool {
+    let label = |key: &str| labels.get(key.trim()).map(String::as_str);
+
+    if let Some(key) = requirement.strip_prefix('!') {
+        return label(key).is_none();
+    }
+
+    if let Some((key, value)) = requirement.split_once("!=") {
+        return label(key) != Some(value.trim());
+    }
+
+    for op in ["==", "="] {
+        if let Some((key, value)) = requirement.split_once(op) {
+            return label(key) == Some(value.trim());
+        }
+    }
+
+    for (op, negated) in [(" notin ", true), (" in ", false)] {
+        if let Some((key, values)) = requirement.split_once(op) {
+            let values = values.trim().trim_start_matches('(').trim_end_matches(')');
+            let found =
+                label(key).is_some_and(|label| values.split(',').any(|v| v.trim() == label));
+
+            return found != negated;
+        }
+    }
+
+    label(requirement).is_some()
+}
+
+fn validate_field_requirement(requirement: &str) -> Result<(), String> {
+    for op in ["==", "!=", "="] {
+        if let Some((key, _)) = requirement.split_once(op) {
+            let key = key.trim();
+            if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric() || c == '.') {
+                return Err(format!("invalid field '{key}'"));
+            }
+
+            return Ok(());
+        }
+    }
+
+    Err(format!(
+        "expected a requirement of the form <field>=<value>, <field>==<value> or <field>!=<value>, got '{requirement}'"
+    ))
+}
+
+/// Validates a label key, which is a name optionally preceded by a DNS subdomain
+/// prefix and a slash, e.g. `app.kubernetes.io/name`
+fn validate_label_key(key: &str) -> Result<(), String> {
+    let name = match key.split_once('/') {
+        Some((prefix, name)) => {
+            let valid_prefix = !prefix.is_empty()
+                && prefix.len() <= 253
+                && prefix.split('.').all(|part| {
+                    !part.is_empty()
+                        && part
+                            .chars()
+                            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+                });
+
+            if !valid_prefix {
+                return Err(format!("invalid label key prefix '{prefix}'"));
+            }
+
+            name
+        }
+        None => key,
+    };
+
+    if name.is_empty() {
+        return Err(format!("invalid label key '{key}'"));
+    }
+
+    validate_label_value(name).map_err(|_| format!("invalid label key '{key}'"))
+}
+
+/// Validates a label value, which must be at most 63 alphanumeric characters, dashes,
+/// underscores or dots, beginning and ending with an alphanumeric character
+fn validate_label_value(value: &str) -> Result<(), String> {
+    if value.is_empty() {
+        return Ok(());
+    }
+
+    let valid = value.len() <= 63
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+        && value.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && value.ends_with(|c: char| c.is_ascii_alphanumeric());
+
+    if valid {
+        Ok(())
+    } else {
+        Err(format!("invalid label value '{value}'"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_label_selectors() {
+        for selector in [
+            "team=payments",
+            "team==payments,tier!=test",
+            "app.kubernetes.io/name=web",
+            "tier in (frontend, backend),env notin (test)",
+            "team,!legacy",
+            "team=",
+        ] {
+            assert_eq!(validate_label_selector(selector), Ok(()), "{selector}");
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_label_selectors() {
+        for selector in [
+            "",
+            "team=pay ments",
+            "team=-payments",
+            "Example.com/team=payments",
+            "/team=payments",
+            "tier in frontend",
+            "tier in (frontend",
+            "tier)",
+        ] {
+            assert!(validate_label_selector(selector).is_err(), "{selector}");
+        }
+    }
+
+    fn labels(labels: &[(&str, &str)]) -> BTreeMap<String, String> {
+        labels
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn matches_label_selectors() {
+        let labels = labels(&[("team", "payments"), ("tier", "web")]);
+
+        for selector in [
+            "team=payments",
+            "team==payments,tier=web",
+            "tier in (web, api)",
+            "tier notin (db)",
+            "team",
+            "!legacy",
+            "team!=search",
+        ] {
+            assert!(label_selector_matches(selector, &labels), "{selector}");
+        }
+
+        for selector in [
+            "team=search",
+            "team=payments,tier=db",
+            "tier in (db)",
+            "tier notin (web)",
+            "legacy",
+            "!team",
+            "team!=payments",
+        ] {
+            assert!(!label_selector_matches(selector, &labels), "{selector}");
+        }
+    }
+
+    #[test]
+    fn negated_requirements_match_missing_labels() {
+        let labels = labels(&[("team", "payments")]);
+
+        assert!(label_selector_matches("tier!=web", &labels));
+        assert!(label_selector_matches("tier notin (web, api)", &labels));
+        assert!(!label_selector_matches("tier=web", &labels));
+        assert!(!label_selector_matches("tier in (web, api)", &labels));
+    }
+}
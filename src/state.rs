@@ -0,0 +1,436 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+
+use chrono::Utc;
+
+use crate::logs::ContainerLogs;
+use crate::resource::ext::cronjob::CronJobExt;
+use crate::resource::ext::daemonset::DaemonSetExt;
+use crate::resource::ext::deployment::DeploymentExt;
+use crate::resource::ext::dynamic::DynamicObjectExt;
+use crate::resource::ext::event::EventExt;
+use crate::resource::ext::job::JobExt;
+use crate::resource::ext::node::NodeExt;
+use crate::resource::ext::pod::PodExt;
+use crate::resource::ext::rollout::RolloutStatus;
+use crate::resource::ext::statefulset::StatefulSetExt;
+use crate::resource::{ChangeType, PackedResource};
+
+/// The fields of a resource which are meaningful enough to notify on when they change
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ResourceState {
+    /// The phase the resource is in, e.g. `Running` for a pod or `Stalled` for a
+    /// deployment
+    pub phase: Option<String>,
+    /// The resource's status conditions, keyed by type
+    pub conditions: BTreeMap<String, String>,
+    /// Whether the resource is schedulable. Only set for nodes
+    pub schedulable: Option<bool>,
+    /// The number of times the resource has occurred. Only set for events
+    pub count: Option<i32>,
+    /// The reason each failing container is failing, e.g. `CrashLoopBackOff`, keyed by
+    /// container name. Only set for pods
+    pub failing_containers: BTreeMap<String, String>,
+}
+
+/// A single field which differs between two [`ResourceState`]s
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StateChange {
+    /// Name of the field that changed, e.g. `Phase` or a condition type
+    pub field: String,
+    /// The previously observed value
+    pub previous: Option<String>,
+    /// The currently observed value
+    pub current: Option<String>,
+}
+
+impl ResourceState {
+    /// Extracts the state of `resource`
+    pub fn new(resource: &PackedResource, cronjob_grace_period: chrono::Duration) -> Self {
+        match resource {
+            PackedResource::Node(node) => Self {
+                conditions: owned_conditions(node.status_conditions()),
+                schedulable: Some(!node.unschedulable()),
+                ..Default::default()
+            },
+            PackedResource::Pod(pod) => Self {
+                phase: pod.phase().cloned(),
+                conditions: owned_conditions(pod.status_conditions()),
+                failing_containers: pod
+                    .failing_containers()
+                    .into_iter()
+                    .filter_map(|c| {
+                        let reason = c.failure_reason()?.clone();
+                        Some((c.name, reason))
+                    })
+                    .collect(),
+                ..Default::default()
+            },
+            PackedResource::Event(event) => Self {
+                phase: event.typ().cloned(),
+                count: event.count(),
+                ..Default::default()
+            },
+            PackedResource::Deployment(deployment) => Self {
+                phase: Some(rollout_phase(&deployment.rollout_status()).to_string()),
+                conditions: owned_conditions(deployment.status_conditions()),
+                ..Default::default()
+            },
+            PackedResource::StatefulSet(stateful_set) => Self {
+                phase: Some(rollout_phase(&stateful_set.rollout_status()).to_string()),
+                conditions: owned_conditions(stateful_set.status_conditions()),
+                ..Default::default()
+            },
+            PackedResource::DaemonSet(daemon_set) => Self {
+                phase: Some(rollout_phase(&daemon_set.rollout_status()).to_string()),
+                conditions: owned_conditions(daemon_set.status_conditions()),
+                ..Default::default()
+            },
+            PackedResource::Job(job) => {
+                let phase = if job.failure_reason().is_some() {
+                    "Failed"
+                } else if job.complete() {
+                    "Complete"
+                } else {
+                    "Running"
+                };
+
+                Self {
+                    phase: Some(phase.to_string()),
+                    conditions: owned_conditions(job.status_conditions()),
+                    ..Default::default()
+                }
+            }
+            PackedResource::CronJob(cron_job) => {
+                let phase = if cron_job.suspended() {
+                    "Suspended"
+                } else if cron_job
+                    .missed_schedule(Utc::now(), cronjob_grace_period)
+                    .is_some()
+                {
+                    "MissedSchedule"
+                } else {
+                    "Scheduled"
+                };
+
+                Self {
+                    phase: Some(phase.to_string()),
+                    ..Default::default()
+                }
+            }
+            PackedResource::Dynamic(obj, _) => Self {
+                conditions: obj
+                    .status_conditions()
+                    .into_iter()
+                    .map(|c| (c.type_, c.status))
+                    .collect(),
+                ..Default::default()
+            },
+        }
+    }
+
+    /// Lists the fields which changed between `previous` and this state
+    pub fn changes_since(&self, previous: &ResourceState) -> Vec<StateChange> {
+        let mut changes = vec![];
+
+        let mut push = |field: &str, previous: Option<String>, current: Option<String>| {
+            if previous != current {
+                changes.push(StateChange {
+                    field: field.to_string(),
+                    previous,
+                    current,
+                });
+            }
+        };
+
+        push("Phase", previous.phase.clone(), self.phase.clone());
+        push(
+            "Schedulable",
+            previous.schedulable.map(|s| s.to_string()),
+            self.schedulable.map(|s| s.to_string()),
+        );
+        push(
+            "Count",
+            previous.count.map(|c| c.to_string()),
+            self.count.map(|c| c.to_string()),
+        );
+
+        let condition_types = previous
+            .conditions
+            .keys()
+            .chain(self.conditions.keys())
+            .collect::<BTreeSet<_>>();
+
+        for typ in condition_types {
+            push(
+                typ,
+                previous.conditions.get(typ).cloned(),
+                self.conditions.get(typ).cloned(),
+            );
+        }
+
+        let containers = previous
+            .failing_containers
+            .keys()
+            .chain(self.failing_containers.keys())
+            .collect::<BTreeSet<_>>();
+
+        for name in containers {
+            push(
+                &format!("Container {name}"),
+                previous.failing_containers.get(name).cloned(),
+                self.failing_containers.get(name).cloned(),
+            );
+        }
+
+        changes
+    }
+}
+
+/// An observed resource whose state has changed since it was last seen
+#[derive(Debug, Clone)]
+pub struct ResourceUpdate {
+    /// How the resource changed
+    pub change: ChangeType,
+    /// The resource as currently observed, or as last observed if it was deleted
+    pub resource: PackedResource,
+    /// The state of the resource when it was last observed, if it has been before
+    pub previous: Option<ResourceState>,
+    /// The current state of the resource
+    pub current: ResourceState,
+    /// Recent logs of the resource's containers which started failing, if it is a pod
+    /// and fetching logs is enabled
+    pub logs: Vec<ContainerLogs>,
+}
+
+impl ResourceUpdate {
+    /// Lists the fields which changed since the resource was last observed. Empty if
+    /// this is the first time the resource has been observed
+    pub fn changes(&self) -> Vec<StateChange> {
+        self.previous
+            .as_ref()
+            .map(|previous| self.current.changes_since(previous))
+            .unwrap_or_default()
+    }
+}
+
+/// Remembers the last observed state of each resource by UID, so that notifications
+/// are only emitted when something meaningful changes rather than on every update
+/// or relist
+pub struct StateTracker {
+    resources: HashMap<String, TrackedResource>,
+    /// Watches which have listed their resources at least once
+    listed: HashSet<String>,
+    cronjob_grace_period: chrono::Duration,
+}
+
+/// A resource as last observed by a watch
+struct TrackedResource {
+    /// Identifies the watch the resource was observed by
+    watch: String,
+    resource: PackedResource,
+    state: ResourceState,
+}
+
+impl StateTracker {
+    pub fn new(cronjob_grace_period: chrono::Duration) -> Self {
+        Self {
+            resources: HashMap::new(),
+            listed: HashSet::new(),
+            cronjob_grace_period,
+        }
+    }
+
+    /// Records the state of `resource` as observed by `watch`, returning an update if
+    /// it has been added, its state has changed since it was last observed or it has
+    /// been deleted
+    pub fn observe(
+        &mut self,
+        watch: &str,
+        change: ChangeType,
+        resource: PackedResource,
+    ) -> Option<ResourceUpdate> {
+        self.record(watch, change, resource, false)
+    }
+
+    /// Records the resources listed when `watch` (re)started, returning updates for
+    /// those whose state has changed and deletions for those tracked for the watch
+    /// which are no longer listed. Resources listed when a watch first starts are
+    /// recorded silently, unless they are already failing
+    pub fn relist(&mut self, watch: &str, resources: Vec<PackedResource>) -> Vec<ResourceUpdate> {
+        let seeding = self.listed.insert(watch.to_string());
+
+        let uids = resources
+            .iter()
+            .filter_map(PackedResource::uid)
+            .collect::<HashSet<_>>();
+
+        let removed = self
+            .resources
+            .iter()
+            .filter(|(uid, tracked)| tracked.watch == watch && !uids.contains(*uid))
+            .map(|(uid, _)| uid.clone())
+            .collect::<Vec<_>>();
+
+        let mut updates = removed
+            .into_iter()
+            .filter_map(|uid| self.resources.remove(&uid))
+            .map(|tracked| ResourceUpdate {
+                change: ChangeType::Deleted,
+                resource: tracked.resource,
+                previous: Some(tracked.state.clone()),
+                current: tracked.state,
+                logs: vec![],
+            })
+            .collect::<Vec<_>>();
+
+        updates.extend(
+            resources.into_iter().filter_map(|resource| {
+                self.record(watch, ChangeType::Restarted, resource, seeding)
+            }),
+        );
+
+        updates
+    }
+
+    /// Forgets every resource observed by `watch` without emitting deletions, e.g.
+    /// when the watch is stopped because its namespace no longer matches. If the watch
+    /// is started again, its first listing is recorded silently
+    pub fn forget(&mut self, watch: &str) {
+        self.resources.retain(|_, tracked| tracked.watch != watch);
+        self.listed.remove(watch);
+    }
+
+    fn record(
+        &mut self,
+        watch: &str,
+        change: ChangeType,
+        resource: PackedResource,
+        seeding: bool,
+    ) -> Option<ResourceUpdate> {
+        let current = ResourceState::new(&resource, self.cronjob_grace_period);
+        let silent = seeding && !is_failing(&resource, &current);
+
+        let Some(uid) = resource.uid() else {
+            // Without a UID the resource can't be tracked, so treat it as changed
+            // unless it is only being seeded
+            if silent {
+                return None;
+            }
+
+            return Some(ResourceUpdate {
+                change,
+                resource,
+                previous: None,
+                current,
+                logs: vec![],
+            });
+        };
+
+        let previous = if change == ChangeType::Deleted {
+            self.resources.remove(&uid).map(|tracked| tracked.state)
+        } else {
+            let tracked = TrackedResource {
+                watch: watch.to_string(),
+                resource: resource.clone(),
+                state: current.clone(),
+            };
+
+            let previous = self
+                .resources
+                .insert(uid, tracked)
+                .map(|tracked| tracked.state);
+            if previous.as_ref() == Some(&current) || (previous.is_none() && silent) {
+                return None;
+            }
+
+            previous
+        };
+
+        Some(ResourceUpdate {
+            change,
+            resource,
+            previous,
+            current,
+            logs: vec![],
+        })
+    }
+}
+
+/// Whether `resource` is unhealthy, e.g. a failed job or a crash looping pod. Events
+/// are never considered failing, since they describe something which happened rather
+/// than the state of a resource
+fn is_failing(resource: &PackedResource, state: &ResourceState) -> bool {
+    if let PackedResource::Dynamic(obj, _) = resource {
+        return obj.status_conditions().iter().any(|c| c.is_failing());
+    }
+
+    !state.failing_containers.is_empty()
+        || state.schedulable == Some(false)
+        || matches!(
+            state.phase.as_deref(),
+            Some("Failed" | "Unknown" | "Stalled" | "MissedSchedule")
+        )
+}
+
+fn owned_conditions(conditions: Option<BTreeMap<&String, &String>>) -> BTreeMap<String, String> {
+    conditions
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(typ, status)| (typ.clone(), status.clone()))
+        .collect()
+}
+
+fn rollout_phase(status: &RolloutStatus) -> &'static str {
+    if status.is_stalled() {
+        "Stalled"
+    } else if status.is_complete() {
+        "Available"
+    } else {
+        "Progressing"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use k8s_openapi::api::core::v1::{Pod, PodStatus};
+    use kube::api::ObjectMeta;
+
+    use super::*;
+
+    fn pod(uid: &str, phase: &str) -> PackedResource {
+        PackedResource::Pod(Pod {
+            metadata: ObjectMeta {
+                name: Some(uid.to_string()),
+                namespace: Some("default".to_string()),
+                uid: Some(uid.to_string()),
+                ..Default::default()
+            },
+            status: Some(PodStatus {
+                phase: Some(phase.to_string()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn forgets_resources_of_stopped_watches() {
+        let mut tracker = StateTracker::new(chrono::Duration::minutes(5));
+        tracker.relist("Pod in a", vec![pod("a", "Running")]);
+        tracker.relist("Pod in b", vec![pod("b", "Running")]);
+
+        tracker.forget("Pod in a");
+
+        assert!(!tracker.resources.contains_key("a"));
+        assert!(tracker.resources.contains_key("b"));
+
+        // Restarting the watch seeds it again rather than reporting every pod as new
+        let updates = tracker.relist("Pod in a", vec![pod("a", "Running")]);
+        assert!(updates.is_empty());
+
+        // Other watches still report deletions
+        let updates = tracker.relist("Pod in b", vec![]);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].change, ChangeType::Deleted);
+    }
+}
@@ -1,131 +1,558 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
 use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
 
+use futures::stream::{AbortHandle, Abortable, SelectAll};
 use futures::{Stream, StreamExt, TryStreamExt};
-use k8s_openapi::api::core::v1::{Event, Node, Pod};
+use k8s_openapi::api::apps::v1::{DaemonSet, Deployment, StatefulSet};
+use k8s_openapi::api::batch::v1::{CronJob, Job};
+use k8s_openapi::api::core::v1::{Event, Namespace, Node, Pod};
+use k8s_openapi::NamespaceResourceScope;
 use kube::{
-    api::Api,
-    runtime::{watcher, WatchStreamExt},
-    Client,
+    api::{Api, ApiResource, DynamicObject, ListParams},
+    discovery::{self, Scope},
+    runtime::{reflector, watcher, WatchStreamExt},
+    Client, Resource, ResourceExt,
 };
+use serde::de::DeserializeOwned;
 use tokio::{sync::broadcast, task::JoinHandle};
-use tracing::error;
+use tokio_stream::wrappers::IntervalStream;
+use tracing::{error, info, warn};
 
+use crate::logs::{ContainerLogFetcher, ContainerLogOptions};
 use crate::namespace::NamespaceScope;
-use crate::resource::{PackedResource, WatchedResource};
+use crate::resource::{ChangeType, PackedResource, WatchedResource};
+use crate::selector::ResourceSelectors;
+use crate::state::{ResourceUpdate, StateTracker};
 
 pub struct ResourceWatcher {
     client: Client,
     namespace_scope: NamespaceScope,
     resources: Vec<WatchedResource>,
+    selectors: ResourceSelectors,
+    log_fetcher: Option<Arc<ContainerLogFetcher>>,
 }
 
-type WatcherOutput = Result<PackedResource, kube::runtime::watcher::Error>;
+/// A change observed by a watch, along with a key identifying the watch
+type WatcherOutput = Result<(String, WatchChange), kube::runtime::watcher::Error>;
+
+type ResourceStream = Pin<Box<dyn Stream<Item = WatcherOutput> + std::marker::Send>>;
+
+type ChangeStream =
+    Pin<Box<dyn Stream<Item = Result<WatchChange, watcher::Error>> + std::marker::Send>>;
+
+/// How often cron jobs are re-emitted, so that missed schedules are noticed even
+/// when the object itself doesn't change
+const CRONJOB_RESYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long to wait for a pod's container logs before notifying without them, so
+/// that later updates of the pod aren't held up indefinitely
+const LOG_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A change observed by a single watch
+#[allow(clippy::large_enum_variant)]
+enum WatchChange {
+    /// An object was added, modified or deleted
+    Object(ChangeType, PackedResource),
+    /// The watch was (re)started, listing every object it watches
+    Relisted(Vec<PackedResource>),
+}
+
+/// A watched resource along with everything needed to create watches for it
+#[derive(Clone)]
+struct ResolvedResource {
+    resource: WatchedResource,
+    api_resource: ApiResource,
+    namespaced: bool,
+    config: watcher::Config,
+}
+
+impl ResolvedResource {
+    /// Identifies the watch of this resource within `namespace`, or across the whole
+    /// cluster if `namespace` is [`None`]
+    fn watch_key(&self, namespace: Option<&str>) -> String {
+        match namespace {
+            Some(namespace) => format!("{} in {namespace}", self.resource),
+            None => self.resource.to_string(),
+        }
+    }
+
+    /// Creates a stream watching this resource within `namespace`, or across the whole
+    /// cluster if `namespace` is [`None`]
+    fn stream(&self, client: Client, namespace: Option<&str>) -> ResourceStream {
+        let config = self.config.clone();
+        let key = self.watch_key(namespace);
+
+        let changes = match &self.resource {
+            WatchedResource::Node => {
+                watch_api(Api::<Node>::all(client), PackedResource::Node, config)
+            }
+            WatchedResource::Pod => {
+                watch_api(api::<Pod>(client, namespace), PackedResource::Pod, config)
+            }
+            WatchedResource::Event => watch_api(
+                api::<Event>(client, namespace),
+                PackedResource::Event,
+                config,
+            ),
+            WatchedResource::Deployment => watch_api(
+                api::<Deployment>(client, namespace),
+                PackedResource::Deployment,
+                config,
+            ),
+            WatchedResource::StatefulSet => watch_api(
+                api::<StatefulSet>(client, namespace),
+                PackedResource::StatefulSet,
+                config,
+            ),
+            WatchedResource::DaemonSet => watch_api(
+                api::<DaemonSet>(client, namespace),
+                PackedResource::DaemonSet,
+                config,
+            ),
+            WatchedResource::Job => {
+                watch_api(api::<Job>(client, namespace), PackedResource::Job, config)
+            }
+            WatchedResource::CronJob => watch_api_with_resync(
+                api::<CronJob>(client, namespace),
+                PackedResource::CronJob,
+                config,
+                CRONJOB_RESYNC_INTERVAL,
+            ),
+            WatchedResource::Dynamic(_) => {
+                let api = match namespace {
+                    Some(namespace) => Api::namespaced_with(client, namespace, &self.api_resource),
+                    None => Api::all_with(client, &self.api_resource),
+                };
+
+                let api_resource = self.api_resource.clone();
+                watch_api(
+                    api,
+                    move |obj| PackedResource::Dynamic(obj, api_resource.clone()),
+                    config,
+                )
+            }
+        };
+
+        changes.map_ok(move |change| (key.clone(), change)).boxed()
+    }
+}
 
 impl ResourceWatcher {
     pub fn new(
         client: Client,
         namespace_scope: NamespaceScope,
         resources: Vec<WatchedResource>,
+        selectors: ResourceSelectors,
+        container_logs: Option<ContainerLogOptions>,
     ) -> Self {
+        let log_fetcher = container_logs
+            .map(|options| Arc::new(ContainerLogFetcher::new(client.clone(), options)));
+
         Self {
             client,
             namespace_scope,
             resources,
+            selectors,
+            log_fetcher,
         }
     }
 
+    /// Resolves a watched resource, discovering it through the API server if it isn't
+    /// one of the built-in resources, and checks its selectors
+    async fn resolve(&self, resource: &WatchedResource) -> anyhow::Result<ResolvedResource> {
+        let (api_resource, namespaced) = match resource {
+            WatchedResource::Node => (ApiResource::erase::<Node>(&()), false),
+            WatchedResource::Pod => (ApiResource::erase::<Pod>(&()), true),
+            WatchedResource::Event => (ApiResource::erase::<Event>(&()), true),
+            WatchedResource::Deployment => (ApiResource::erase::<Deployment>(&()), true),
+            WatchedResource::StatefulSet => (ApiResource::erase::<StatefulSet>(&()), true),
+            WatchedResource::DaemonSet => (ApiResource::erase::<DaemonSet>(&()), true),
+            WatchedResource::Job => (ApiResource::erase::<Job>(&()), true),
+            WatchedResource::CronJob => (ApiResource::erase::<CronJob>(&()), true),
+            WatchedResource::Dynamic(gvk) => {
+                let (ar, caps) = discovery::pinned_kind(&self.client, gvk)
+                    .await
+                    .map_err(|e| anyhow::anyhow!("Failed to discover resource {resource}: {e}"))?;
+
+                (ar, caps.scope == Scope::Namespaced)
+            }
+        };
+
+        let resolved = ResolvedResource {
+            resource: resource.clone(),
+            api_resource,
+            namespaced,
+            config: self.selectors.watcher_config(resource),
+        };
+
+        if resolved.config.label_selector.is_some() || resolved.config.field_selector.is_some() {
+            self.check_selectors(&resolved).await?;
+        }
+
+        Ok(resolved)
+    }
+
+    /// Lists objects matching the selectors of `resolved`, so that selectors the API
+    /// server rejects (e.g. an unsupported field) are reported at startup
+    async fn check_selectors(&self, resolved: &ResolvedResource) -> anyhow::Result<()> {
+        let api: Api<DynamicObject> = match &self.namespace_scope {
+            NamespaceScope::Names(names) if resolved.namespaced && !names.is_empty() => {
+                Api::namespaced_with(self.client.clone(), &names[0], &resolved.api_resource)
+            }
+            _ => Api::all_with(self.client.clone(), &resolved.api_resource),
+        };
+
+        let params = ListParams {
+            label_selector: resolved.config.label_selector.clone(),
+            field_selector: resolved.config.field_selector.clone(),
+            limit: Some(1),
+            ..Default::default()
+        };
+
+        api.list_metadata(&params).await.map_err(|e| {
+            anyhow::anyhow!("Invalid selectors for resource {}: {e}", resolved.resource)
+        })?;
+
+        Ok(())
+    }
+
+    /// Creates streams for every resource which can be watched up front. When
+    /// namespaces are chosen by a selector, namespaced resources are instead watched
+    /// as matching namespaces appear
     fn create_multiplexed_resource_stream(
         &self,
-    ) -> futures::stream::SelectAll<Pin<Box<dyn Stream<Item = WatcherOutput> + std::marker::Send>>>
-    {
-        let mut streams = vec![];
-        for resource in &self.resources {
-            match resource {
-                WatchedResource::Node => {
-                    let nodes: Api<Node> = Api::all(self.client.clone());
-                    streams.push(
-                        watcher(nodes, watcher::Config::default())
-                            .default_backoff()
-                            .applied_objects()
-                            .map_ok(PackedResource::Node)
-                            .boxed(),
-                    );
+        resources: &[ResolvedResource],
+    ) -> SelectAll<ResourceStream> {
+        let mut streams = SelectAll::new();
+
+        for resource in resources {
+            if !resource.namespaced {
+                streams.push(resource.stream(self.client.clone(), None));
+                continue;
+            }
+
+            match &self.namespace_scope {
+                NamespaceScope::All { .. } => {
+                    streams.push(resource.stream(self.client.clone(), None))
                 }
-                WatchedResource::Pod => match &self.namespace_scope {
-                    NamespaceScope::All => {
-                        let pods: Api<Pod> = Api::all(self.client.clone());
-                        streams.push(
-                            watcher(pods, watcher::Config::default())
-                                .default_backoff()
-                                .applied_objects()
-                                .map_ok(PackedResource::Pod)
-                                .boxed(),
-                        )
-                    }
-                    NamespaceScope::Names(names) => {
-                        streams.extend(names.iter().map(|name| {
-                            let pods: Api<Pod> =
-                                Api::namespaced(self.client.clone(), name.as_str());
-
-                            watcher(pods, watcher::Config::default())
-                                .default_backoff()
-                                .applied_objects()
-                                .map_ok(PackedResource::Pod)
-                                .boxed()
-                        }));
-                    }
-                },
-                WatchedResource::Event => match &self.namespace_scope {
-                    NamespaceScope::All => {
-                        let pods: Api<Event> = Api::all(self.client.clone());
-                        streams.push(
-                            watcher(pods, watcher::Config::default())
-                                .default_backoff()
-                                .applied_objects()
-                                .map_ok(PackedResource::Event)
-                                .boxed(),
-                        )
+                NamespaceScope::Names(names) => {
+                    for name in names {
+                        streams.push(resource.stream(self.client.clone(), Some(name)));
                     }
-                    NamespaceScope::Names(names) => {
-                        streams.extend(names.iter().map(|name| {
-                            let pods: Api<Event> =
-                                Api::namespaced(self.client.clone(), name.as_str());
-
-                            watcher(pods, watcher::Config::default())
-                                .default_backoff()
-                                .applied_objects()
-                                .map_ok(PackedResource::Event)
-                                .boxed()
-                        }));
-                    }
-                },
+                }
+                NamespaceScope::Selector { .. } => {}
             }
         }
 
-        futures::stream::select_all(streams)
+        streams
+    }
+
+    /// Watches namespaces matching the scope's selector, or never yields if namespaces
+    /// aren't chosen by a selector
+    fn namespace_stream(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = Result<watcher::Event<Namespace>, watcher::Error>> + Send>> {
+        match &self.namespace_scope {
+            NamespaceScope::Selector { selector, .. } => {
+                let api: Api<Namespace> = Api::all(self.client.clone());
+                watcher(api, watcher::Config::default().labels(selector))
+                    .default_backoff()
+                    .boxed()
+            }
+            _ => futures::stream::pending().boxed(),
+        }
     }
 
-    pub fn watch(&self) -> (JoinHandle<()>, broadcast::Sender<PackedResource>) {
-        let mut stream = self.create_multiplexed_resource_stream();
+    /// Starts watching all registered resources, broadcasting updates on the returned
+    /// channel whenever `tracker` observes a meaningful change in a resource's state.
+    /// Resources in excluded namespaces are dropped here, since cluster-wide watches
+    /// can't exclude namespaces server-side. Fails if any arbitrary resources cannot be
+    /// resolved through API discovery
+    pub async fn watch(
+        &self,
+        mut tracker: StateTracker,
+    ) -> anyhow::Result<(JoinHandle<()>, broadcast::Sender<ResourceUpdate>)> {
+        self.selectors.validate(&self.resources)?;
+
+        let mut resources = vec![];
+        for resource in &self.resources {
+            resources.push(self.resolve(resource).await?);
+        }
+
+        let mut stream = self.create_multiplexed_resource_stream(&resources);
+        let mut namespaces = self.namespace_stream();
+        let mut namespace_watches = NamespaceWatches {
+            client: self.client.clone(),
+            namespace_scope: self.namespace_scope.clone(),
+            resources: resources.into_iter().filter(|r| r.namespaced).collect(),
+            active: HashMap::new(),
+        };
+        let namespace_scope = self.namespace_scope.clone();
+
         let (tx, _) = broadcast::channel(256);
 
-        let inner_tx = tx.clone();
+        let mut updates_tx = UpdateSender {
+            tx: tx.clone(),
+            log_fetcher: self.log_fetcher.clone(),
+            pending: HashMap::new(),
+        };
 
         let handle = tokio::spawn(async move {
-            while let Some(resource) = stream.next().await {
-                match resource {
-                    Ok(resource) => {
-                        if let Err(e) = inner_tx.send(resource) {
-                            error!("Error broadcasting resource update {:?}", e);
+            loop {
+                tokio::select! {
+                    Some(resource) = stream.next() => match resource {
+                        Ok((watch, change)) => {
+                            let included = |resource: &PackedResource| {
+                                namespace_scope.includes(resource.namespace().as_deref())
+                            };
+
+                            let updates = match change {
+                                WatchChange::Object(change, resource) if included(&resource) => {
+                                    tracker.observe(&watch, change, resource).into_iter().collect()
+                                }
+                                WatchChange::Object(..) => vec![],
+                                WatchChange::Relisted(resources) => tracker.relist(
+                                    &watch,
+                                    resources.into_iter().filter(included).collect(),
+                                ),
+                            };
+
+                            for update in updates {
+                                updates_tx.send(update);
+                            }
                         }
-                    }
-                    Err(e) => {
-                        error!("Received error while reading from resource stream {:?}", e);
-                    }
+                        Err(e) => {
+                            error!("Received error while reading from resource stream {:?}", e);
+                        }
+                    },
+                    Some(event) = namespaces.next() => match event {
+                        Ok(event) => {
+                            namespace_watches.apply(event, &mut stream, &mut tracker)
+                        }
+                        Err(e) => {
+                            error!("Received error while reading from namespace stream {:?}", e);
+                        }
+                    },
+                    else => break,
+                }
+            }
+        });
+
+        Ok((handle, tx))
+    }
+}
+
+/// Sends `update` to every notifier, logging if none are listening
+fn broadcast_update(tx: &broadcast::Sender<ResourceUpdate>, update: ResourceUpdate) {
+    if let Err(e) = tx.send(update) {
+        error!("Error broadcasting resource update {:?}", e);
+    }
+}
+
+/// Broadcasts updates to notifiers, attaching container logs where wanted. Fetching
+/// logs takes a round trip to the API server, so it's done in the background to keep
+/// watching, while updates for the same object are still broadcast in order
+struct UpdateSender {
+    tx: broadcast::Sender<ResourceUpdate>,
+    log_fetcher: Option<Arc<ContainerLogFetcher>>,
+    /// The latest background task of each object which is fetching logs or waiting
+    /// on an earlier update of the same object, keyed by UID
+    pending: HashMap<String, JoinHandle<()>>,
+}
+
+impl UpdateSender {
+    fn send(&mut self, mut update: ResourceUpdate) {
+        self.pending.retain(|_, task| !task.is_finished());
+
+        let uid = update.resource.uid();
+        let earlier = uid.as_ref().and_then(|uid| self.pending.remove(uid));
+        let log_fetcher = self
+            .log_fetcher
+            .clone()
+            .filter(|_| ContainerLogFetcher::wants_logs(&update));
+
+        if earlier.is_none() && log_fetcher.is_none() {
+            broadcast_update(&self.tx, update);
+            return;
+        }
+
+        let tx = self.tx.clone();
+        let task = tokio::spawn(async move {
+            if let Some(earlier) = earlier {
+                let _ = earlier.await;
+            }
+
+            if let Some(log_fetcher) = log_fetcher {
+                if tokio::time::timeout(LOG_FETCH_TIMEOUT, log_fetcher.attach(&mut update))
+                    .await
+                    .is_err()
+                {
+                    warn!("Timed out fetching container logs, sending notification without them");
                 }
             }
+
+            broadcast_update(&tx, update);
         });
 
-        (handle, tx)
+        if let Some(uid) = uid {
+            self.pending.insert(uid, task);
+        }
+    }
+}
+
+/// The per-namespace watches started for namespaces matching a
+/// [`NamespaceScope::Selector`]
+struct NamespaceWatches {
+    client: Client,
+    namespace_scope: NamespaceScope,
+    /// Namespaced resources to watch within each matching namespace
+    resources: Vec<ResolvedResource>,
+    /// Handles stopping the watches of each matching namespace
+    active: HashMap<String, AbortHandle>,
+}
+
+impl NamespaceWatches {
+    /// Starts or stops watches as namespaces begin or stop matching the selector. The
+    /// resources of stopped watches are forgotten by `tracker`
+    fn apply(
+        &mut self,
+        event: watcher::Event<Namespace>,
+        streams: &mut SelectAll<ResourceStream>,
+        tracker: &mut StateTracker,
+    ) {
+        match event {
+            watcher::Event::Applied(namespace) => self.start(namespace.name_any(), streams),
+            watcher::Event::Deleted(namespace) => self.stop(&namespace.name_any(), tracker),
+            watcher::Event::Restarted(namespaces) => {
+                let names = namespaces
+                    .iter()
+                    .map(|namespace| namespace.name_any())
+                    .collect::<Vec<_>>();
+
+                let stale = self
+                    .active
+                    .keys()
+                    .filter(|name| !names.contains(name))
+                    .cloned()
+                    .collect::<Vec<_>>();
+
+                for name in stale {
+                    self.stop(&name, tracker);
+                }
+
+                for name in names {
+                    self.start(name, streams);
+                }
+            }
+        }
+    }
+
+    fn start(&mut self, namespace: String, streams: &mut SelectAll<ResourceStream>) {
+        if self.active.contains_key(&namespace) || self.namespace_scope.excludes(&namespace) {
+            return;
+        }
+
+        info!("Watching resources in namespace {namespace}");
+
+        let (handle, registration) = AbortHandle::new_pair();
+        let namespace_streams = futures::stream::select_all(
+            self.resources
+                .iter()
+                .map(|resource| resource.stream(self.client.clone(), Some(&namespace))),
+        );
+
+        streams.push(Abortable::new(namespace_streams, registration).boxed());
+        self.active.insert(namespace, handle);
+    }
+
+    fn stop(&mut self, namespace: &str, tracker: &mut StateTracker) {
+        if let Some(handle) = self.active.remove(namespace) {
+            info!("Stopped watching resources in namespace {namespace}");
+            handle.abort();
+
+            for resource in &self.resources {
+                tracker.forget(&resource.watch_key(Some(namespace)));
+            }
+        }
+    }
+}
+
+/// Creates an API for a namespaced resource within `namespace`, or across the whole
+/// cluster if `namespace` is [`None`]
+fn api<K>(client: Client, namespace: Option<&str>) -> Api<K>
+where
+    K: Resource<Scope = NamespaceResourceScope, DynamicType = ()>,
+{
+    match namespace {
+        Some(namespace) => Api::namespaced(client, namespace),
+        None => Api::all(client),
     }
 }
+
+/// Watches objects from `api`, packing each changed object into a [`PackedResource`]
+fn watch_api<K>(
+    api: Api<K>,
+    pack: impl Fn(K) -> PackedResource + Send + 'static,
+    config: watcher::Config,
+) -> ChangeStream
+where
+    K: Resource + Clone + DeserializeOwned + Debug + Send + 'static,
+{
+    resource_changes(watcher(api, config).default_backoff(), pack).boxed()
+}
+
+/// Like [`watch_api`], but additionally re-emits every known object each `period`.
+/// Useful for resources whose state depends on the passage of time
+fn watch_api_with_resync<K>(
+    api: Api<K>,
+    pack: fn(K) -> PackedResource,
+    config: watcher::Config,
+    period: Duration,
+) -> ChangeStream
+where
+    K: Resource<DynamicType = ()> + Clone + DeserializeOwned + Debug + Send + Sync + 'static,
+{
+    let (store, writer) = reflector::store();
+
+    let watch = resource_changes(
+        reflector(writer, watcher(api, config)).default_backoff(),
+        pack,
+    );
+
+    let resync = IntervalStream::new(tokio::time::interval(period)).flat_map(move |_| {
+        futures::stream::iter(
+            store
+                .state()
+                .into_iter()
+                .map(|obj| {
+                    Ok(WatchChange::Object(
+                        ChangeType::Applied,
+                        pack(obj.as_ref().clone()),
+                    ))
+                })
+                .collect::<Vec<_>>(),
+        )
+    });
+
+    futures::stream::select(watch, resync).boxed()
+}
+
+/// Converts watch events into changes, packing each object into a [`PackedResource`]
+fn resource_changes<K>(
+    events: impl Stream<Item = Result<watcher::Event<K>, watcher::Error>> + Send,
+    pack: impl Fn(K) -> PackedResource + Send,
+) -> impl Stream<Item = Result<WatchChange, watcher::Error>> + Send
+where
+    K: Send,
+{
+    events.map_ok(move |event| match event {
+        watcher::Event::Applied(obj) => WatchChange::Object(ChangeType::Applied, pack(obj)),
+        watcher::Event::Deleted(obj) => WatchChange::Object(ChangeType::Deleted, pack(obj)),
+        watcher::Event::Restarted(objs) => {
+            WatchChange::Relisted(objs.into_iter().map(&pack).collect())
+        }
+    })
+}